@@ -0,0 +1,151 @@
+// times the vector kernels on F4 against the plain code, then renders
+// scenes/sphere_scene.yml with packets in SSE registers and in plain arrays,
+// and with packets and with every ray on its own
+//
+//   cargo bench --bench sphere_scene
+//
+// with --features scalar both kinds of packets are plain arrays
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use ray_tracer::color::Color;
+use ray_tracer::render::{render, RenderOptions};
+use ray_tracer::scene::{self, Scene};
+use ray_tracer::geometry::Vector3;
+use ray_tracer::matrix4::Matrix4;
+use ray_tracer::simd::{self, F4, Lanes};
+use ray_tracer::Float;
+
+const N: usize = 4096;
+const ROUNDS: usize = 2000;
+// the renders take long enough to be disturbed, so they are repeated more
+const RENDER_RUNS: usize = 15;
+
+fn time<F: FnMut()>(f: F) -> Duration {
+    time_runs(5, f)
+}
+
+// once to warm up, then the fastest of the runs
+fn time_runs<F: FnMut()>(runs: usize, mut f: F) -> Duration {
+    f();
+    (0..runs)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn report(name: &str, simd: Duration, scalar: Duration) {
+    let per_op = |d: Duration| d.as_secs_f64() * 1e9 / (N * ROUNDS) as f64;
+    println!("{:<12} {:>8.2} ns  scalar {:>8.2} ns  {:>5.2}x",
+        name, per_op(simd), per_op(scalar), scalar.as_secs_f64() / simd.as_secs_f64());
+}
+
+fn inputs() -> Vec<[Float; 4]> {
+    (0..N).map(|i| {
+        let f = i as Float;
+        [f.sin(), f.cos(), (f * 0.5).sin(), 0.0]
+    }).collect()
+}
+
+fn kernels() {
+    let v = inputs();
+    let m = Matrix4::new([
+        0.9, -0.1, 0.3, 1.0,
+        0.2, 0.8, -0.4, 2.0,
+        -0.3, 0.5, 0.7, -1.0,
+        0.0, 0.0, 0.0, 1.0,
+    ]);
+    let vectors = v.iter().map(|a| Vector3::new(a[0], a[1], a[2])).collect::<Vec<_>>();
+    let colors = v.iter().map(|a| Color::new(a[0], a[1], a[2])).collect::<Vec<_>>();
+    let lanes = |v: Vector3| F4::new(v.x, v.y, v.z, 0.0);
+    let from_lanes = |l: F4| {
+        let [x, y, z, _] = l.to_array();
+        Vector3::new(x, y, z)
+    };
+
+    // both sides take and return the same types, so the F4 side pays for
+    // moving the fields into lanes and back like it would in the library
+    macro_rules! bench {
+        ($xs:expr, $body:expr) => {
+            time(|| {
+                for _ in 0..ROUNDS {
+                    for w in black_box(&$xs).windows(2) {
+                        black_box($body(w[0], w[1]));
+                    }
+                }
+            })
+        };
+    }
+
+    report("dot",
+        bench!(vectors, |a: Vector3, b: Vector3| lanes(a).dot3(lanes(b))),
+        bench!(vectors, |a: Vector3, b: Vector3| a.x * b.x + a.y * b.y + a.z * b.z));
+    report("cross",
+        bench!(vectors, |a: Vector3, b: Vector3| from_lanes(lanes(a).cross3(lanes(b)))),
+        bench!(vectors, |a: Vector3, b: Vector3| Vector3::new(
+            a.y * b.z - a.z * b.y,
+            a.z * b.x - a.x * b.z,
+            a.x * b.y - a.y * b.x,
+        )));
+    report("matrix",
+        bench!(vectors, |a: Vector3, _| from_lanes(F4::mul_matrix(black_box(&m).as_array(), lanes(a)))),
+        bench!(vectors, |a: Vector3, _| {
+            let m = black_box(&m);
+            Vector3::new(
+                m[(0, 0)] * a.x + m[(0, 1)] * a.y + m[(0, 2)] * a.z,
+                m[(1, 0)] * a.x + m[(1, 1)] * a.y + m[(1, 2)] * a.z,
+                m[(2, 0)] * a.x + m[(2, 1)] * a.y + m[(2, 2)] * a.z,
+            )
+        }));
+
+    // the chain of products and sums of lightning_filtered
+    report("color",
+        bench!(colors, |a: Color<Float>, b: Color<Float>| {
+            let (a, b) = (a.lanes(), b.lanes());
+            Color::from_lanes(a * F4::splat(0.1) + (a * F4::splat(0.9) + b * F4::splat(0.5)) * b)
+        }),
+        bench!(colors, |a: Color<Float>, b: Color<Float>| a * 0.1 + (a * 0.9 + b * 0.5) * b));
+}
+
+fn render_time(scene: &Scene, packets: bool) -> Duration {
+    let mut options = RenderOptions::new();
+    options.packets = packets;
+    time_runs(RENDER_RUNS, || {
+        black_box(render(&scene.world, &scene.camera, &options));
+    })
+}
+
+// like render with packets, on this thread and with the packets in L
+fn render_lanes_time<L: Lanes>(scene: &Scene) -> Duration {
+    let (camera, world) = (&scene.camera, &scene.world);
+    time_runs(RENDER_RUNS, || {
+        for y in (0..camera.height()).step_by(2) {
+            for x in (0..camera.width()).step_by(2) {
+                black_box(camera.render_block_with::<L>(world, x, y));
+            }
+        }
+    })
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1e3
+}
+
+fn main() {
+    kernels();
+
+    let mut scene = scene::load_file("scenes/sphere_scene.yml").expect("can't load the scene");
+    scene.camera.set_size(400.0, 200.0);
+
+    let (sse, scalar) = (render_lanes_time::<F4>(&scene), render_lanes_time::<simd::scalar::F4>(&scene));
+    let mode = if cfg!(feature = "scalar") { "scalar" } else { "simd" };
+    println!("sphere_scene 400x200 ({}) packets {:>6.1} ms  scalar packets {:>6.1} ms  {:>5.2}x",
+        mode, ms(sse), ms(scalar), scalar.as_secs_f64() / sse.as_secs_f64());
+
+    let (packets, single) = (render_time(&scene, true), render_time(&scene, false));
+    println!("sphere_scene 400x200 ({}) packets {:>6.1} ms  single rays {:>6.1} ms  {:>5.2}x",
+        mode, ms(packets), ms(single), single.as_secs_f64() / packets.as_secs_f64());
+}
@@ -0,0 +1,100 @@
+// arbitrary output variables, what the primary rays hit besides the shaded color,
+// for compositing: relighting, denoising and selection masks
+use crate::color::{Canvas, Color};
+use crate::geometry::Vector3;
+use crate::Float;
+
+// what one pixel saw, all zero where nothing is hit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AovSample {
+    // along the view direction of the camera, not along the ray
+    pub depth: Float,
+    // in world space, facing the camera
+    pub normal: Vector3,
+    // Material::color, without any light
+    pub albedo: Color<Float>,
+    // 1 + the index of the object in World::objects, 0 is the background
+    pub id: usize,
+}
+
+impl AovSample {
+    pub fn none() -> Self {
+        Self {
+            depth: 0.0,
+            normal: Vector3::new(0.0, 0.0, 0.0),
+            albedo: Color::new(0.0, 0.0, 0.0),
+            id: 0,
+        }
+    }
+}
+
+// one Canvas per pass, the same size as the image
+pub struct Aovs {
+    pub depth: Canvas,
+    pub normal: Canvas,
+    pub albedo: Canvas,
+    // the id in every channel
+    pub id: Canvas,
+}
+
+// a color that tells neighbouring ids apart, black for the background
+fn id_color(id: usize) -> Color<f32> {
+    if id == 0 {
+        return Color::new(0.0, 0.0, 0.0)
+    }
+    let mut h = (id as u32).wrapping_mul(0x9e37_79b9);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    let channel = |shift: u32| 0.25 + 0.75 * ((h >> shift) & 0xff) as f32 / 255.0;
+    Color::new(channel(0), channel(8), channel(16))
+}
+
+impl Aovs {
+    pub fn new(w: usize, h: usize) -> Self {
+        Self {
+            depth: Canvas::new(w, h),
+            normal: Canvas::new(w, h),
+            albedo: Canvas::new(w, h),
+            id: Canvas::new(w, h),
+        }
+    }
+
+    pub fn write_sample(&mut self, x: usize, y: usize, s: &AovSample) {
+        self.depth.write_pixel(x, y, Color::new(s.depth, s.depth, s.depth).to_f32());
+        self.normal.write_pixel(x, y, Color::new(s.normal.x, s.normal.y, s.normal.z).to_f32());
+        self.albedo.write_pixel(x, y, s.albedo.to_f32());
+        let id = s.id as f32;
+        self.id.write_pixel(x, y, Color::new(id, id, id));
+    }
+
+    pub fn id_at(&self, x: usize, y: usize) -> usize {
+        self.id.pixel_at(x, y).0 as usize
+    }
+
+    // white where the pixel shows the object with this id, black elsewhere
+    pub fn mask(&self, id: usize) -> Canvas {
+        Canvas::new_fn(self.id.w, self.id.h, |x, y| {
+            if self.id_at(x, y) == id { Color::new(1.0, 1.0, 1.0) } else { Color::new(0.0, 0.0, 0.0) }
+        })
+    }
+
+    // the passes in [0, 1] for the formats that can't store anything else,
+    // depth goes from 0 to the farthest hit and every id gets its own color
+    pub fn visible(&self) -> Aovs {
+        let (w, h) = (self.depth.w, self.depth.h);
+        let far = (0..w * h)
+            .map(|i| self.depth.pixel_at(i % w, i / w).0)
+            .fold(0.0, f32::max);
+        let scale = if far > 0.0 { 1.0 / far } else { 0.0 };
+        Aovs {
+            depth: Canvas::new_fn(w, h, |x, y| self.depth.pixel_at(x, y) * scale),
+            normal: Canvas::new_fn(w, h, |x, y| {
+                let n = self.normal.pixel_at(x, y);
+                Color::new(n.0 * 0.5 + 0.5, n.1 * 0.5 + 0.5, n.2 * 0.5 + 0.5)
+            }),
+            albedo: Canvas::new_fn(w, h, |x, y| self.albedo.pixel_at(x, y)),
+            id: Canvas::new_fn(w, h, |x, y| id_color(self.id_at(x, y))),
+        }
+    }
+}
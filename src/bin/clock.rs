@@ -24,7 +24,7 @@ fn main() {
                 .rotation_z(angle)
                 .translate(middle_x, middle_y, 0.0)
                 .build();
-            &m * east
+            m * east
         })
         .collect();
 
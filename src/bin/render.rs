@@ -0,0 +1,286 @@
+use ray_tracer::scene;
+use ray_tracer::render::{render, render_aovs, RenderOptions};
+use ray_tracer::aov::Aovs;
+use ray_tracer::denoise::{denoise, DenoiseOptions};
+use ray_tracer::ppm::PpmFormat;
+use ray_tracer::png::{BitDepth, PngOptions};
+use ray_tracer::tonemap::{DisplayTransform, ToneMap};
+use ray_tracer::color::Canvas;
+use ray_tracer::Float;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::process;
+use std::time::Instant;
+
+const USAGE: &str = "usage: render <scene.yml> [options]
+
+options:
+  -o, --output <file>    where to write the image, stdout if not given
+  -W, --width <n>        override the camera width
+  -H, --height <n>       override the camera height
+  -s, --samples <n>      samples per pixel
+  -t, --threads <n>      number of render threads
+  -f, --format <fmt>     ppm, p6, png, png16, hdr or pfm, guessed from the output name
+      --tone <op>        clamp, exposure, reinhard or aces (default clamp)
+      --exposure <ev>    exposure in stops (default 0)
+      --linear           don't apply the sRGB curve
+      --aovs <prefix>    also write <prefix>.depth, .normal, .albedo and .id in the
+                         same format, remapped into [0, 1] unless it is hdr or pfm
+      --denoise          filter the noise out, guided by the albedo and normal passes
+  -q, --quiet            no progress or statistics
+  -h, --help             show this help";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Ppm,
+    P6,
+    Png,
+    Png16,
+    Hdr,
+    Pfm,
+}
+
+struct Args {
+    scene: String,
+    output: Option<String>,
+    width: Option<usize>,
+    height: Option<usize>,
+    samples: Option<usize>,
+    threads: Option<usize>,
+    format: Option<Format>,
+    tone: ToneMap,
+    exposure: f32,
+    linear: bool,
+    aovs: Option<String>,
+    denoise: bool,
+    quiet: bool,
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("error: {}", msg);
+    process::exit(1)
+}
+
+fn parse_format(s: &str) -> Option<Format> {
+    match s.to_lowercase().as_str() {
+        "ppm" | "p3" => Some(Format::Ppm),
+        "p6" => Some(Format::P6),
+        "png" => Some(Format::Png),
+        "png16" => Some(Format::Png16),
+        "hdr" => Some(Format::Hdr),
+        "pfm" => Some(Format::Pfm),
+        _ => None,
+    }
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        scene: String::new(),
+        output: None,
+        width: None,
+        height: None,
+        samples: None,
+        threads: None,
+        format: None,
+        tone: ToneMap::Clamp,
+        exposure: 0.0,
+        linear: false,
+        aovs: None,
+        denoise: false,
+        quiet: false,
+    };
+
+    let mut it = std::env::args().skip(1);
+    let mut scene = None;
+    while let Some(arg) = it.next() {
+        let mut value = |name: &str| match it.next() {
+            Some(v) => v,
+            None => fail(&format!("{} needs a value", name)),
+        };
+        let mut number = |name: &str| {
+            let v = value(name);
+            match v.parse::<usize>() {
+                Ok(n) if n > 0 => n,
+                _ => fail(&format!("{} needs a positive number, got '{}'", name, v)),
+            }
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0)
+            },
+            "-o" | "--output" => args.output = Some(value(&arg)),
+            "-W" | "--width" => args.width = Some(number(&arg)),
+            "-H" | "--height" => args.height = Some(number(&arg)),
+            "-s" | "--samples" => args.samples = Some(number(&arg)),
+            "-t" | "--threads" => args.threads = Some(number(&arg)),
+            "-f" | "--format" => {
+                let v = value(&arg);
+                args.format = Some(parse_format(&v).unwrap_or_else(|| fail(&format!("unknown format '{}'", v))));
+            },
+            "--tone" => {
+                args.tone = match value(&arg).as_str() {
+                    "clamp" => ToneMap::Clamp,
+                    "exposure" => ToneMap::Exposure,
+                    "reinhard" => ToneMap::Reinhard,
+                    "aces" => ToneMap::AcesFilmic,
+                    v => fail(&format!("unknown tone map '{}'", v)),
+                }
+            },
+            "--exposure" => {
+                let v = value(&arg);
+                args.exposure = v.parse().unwrap_or_else(|_| fail(&format!("bad exposure '{}'", v)));
+            },
+            "--linear" => args.linear = true,
+            "--aovs" => args.aovs = Some(value(&arg)),
+            "--denoise" => args.denoise = true,
+            "-q" | "--quiet" => args.quiet = true,
+            _ if arg.starts_with('-') => fail(&format!("unknown option '{}'\n\n{}", arg, USAGE)),
+            _ if scene.is_none() => scene = Some(arg),
+            _ => fail(&format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    args.scene = scene.unwrap_or_else(|| fail(USAGE));
+    args
+}
+
+fn write_image<W: Write>(canvas: &Canvas, format: Format, display: &DisplayTransform, out: &mut W) -> std::io::Result<()> {
+    match format {
+        Format::Ppm => canvas.write_ppm_to(out, PpmFormat::Ascii, display),
+        Format::P6 => canvas.write_ppm_to(out, PpmFormat::Binary, display),
+        Format::Png | Format::Png16 => {
+            let mut options = PngOptions::new();
+            options.display = display.clone();
+            if format == Format::Png16 {
+                options.bit_depth = BitDepth::Sixteen;
+            }
+            out.write_all(&canvas.write_png(&options))?;
+            out.flush()
+        },
+        Format::Hdr => canvas.write_hdr_to(out),
+        Format::Pfm => canvas.write_pfm_to(out),
+    }
+}
+
+fn extension(format: Format) -> &'static str {
+    match format {
+        Format::Ppm | Format::P6 => "ppm",
+        Format::Png | Format::Png16 => "png",
+        Format::Hdr => "hdr",
+        Format::Pfm => "pfm",
+    }
+}
+
+fn write_aovs(aovs: &Aovs, prefix: &str, format: Format) -> std::io::Result<()> {
+    // the float formats keep the values as they are
+    let visible;
+    let aovs = match format {
+        Format::Hdr | Format::Pfm => aovs,
+        _ => {
+            visible = aovs.visible();
+            &visible
+        },
+    };
+    let passes = [("depth", &aovs.depth), ("normal", &aovs.normal), ("albedo", &aovs.albedo), ("id", &aovs.id)];
+    for (name, canvas) in passes.iter() {
+        let path = format!("{}.{}.{}", prefix, name, extension(format));
+        let file = File::create(&path).map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+        write_image(canvas, format, &DisplayTransform::new(), &mut BufWriter::new(file))?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = parse_args();
+
+    if let Some(n) = args.threads {
+        rayon::ThreadPoolBuilder::new().num_threads(n).build_global()
+            .unwrap_or_else(|e| fail(&e.to_string()));
+    }
+
+    let start = Instant::now();
+    let mut scene = scene::load_file(&args.scene)
+        .unwrap_or_else(|e| fail(&format!("{}: {}", args.scene, e)));
+    let load_time = start.elapsed();
+
+    let (w, h) = (
+        args.width.unwrap_or(scene.camera.width()),
+        args.height.unwrap_or(scene.camera.height()),
+    );
+    scene.camera.set_size(w as Float, h as Float);
+    if let Some(samples) = args.samples {
+        scene.camera.samples = samples;
+    }
+
+    let format = args.format.unwrap_or_else(|| {
+        args.output.as_ref()
+            .and_then(|o| o.rsplit('.').next())
+            .and_then(parse_format)
+            .unwrap_or(Format::Ppm)
+    });
+
+    let mut options = RenderOptions::new();
+    if !args.quiet {
+        options.progress = Some(Box::new(|done, total| {
+            eprint!("\rrendering {:3}%", done * 100 / total);
+        }));
+    }
+
+    let start = Instant::now();
+    // the denoiser needs the passes too
+    let (mut canvas, aovs) = if args.aovs.is_some() || args.denoise {
+        let (canvas, aovs) = render_aovs(&scene.world, &scene.camera, &options);
+        (canvas, Some(aovs))
+    } else {
+        (render(&scene.world, &scene.camera, &options), None)
+    };
+    let render_time = start.elapsed();
+    if !args.quiet {
+        eprintln!();
+    }
+
+    let start = Instant::now();
+    if let (true, Some(aovs)) = (args.denoise, &aovs) {
+        canvas = denoise(&canvas, aovs, &DenoiseOptions::new());
+    }
+    let denoise_time = start.elapsed();
+
+    let display = DisplayTransform {
+        exposure: args.exposure,
+        tone_map: args.tone,
+        srgb: !args.linear,
+    };
+
+    let start = Instant::now();
+    let result = match &args.output {
+        Some(path) => {
+            let file = File::create(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+            write_image(&canvas, format, &display, &mut BufWriter::new(file))
+        },
+        None => {
+            let stdout = std::io::stdout();
+            let mut out = BufWriter::new(stdout.lock());
+            write_image(&canvas, format, &display, &mut out)
+        },
+    };
+    result.unwrap_or_else(|e| fail(&e.to_string()));
+    if let (Some(aovs), Some(prefix)) = (&aovs, &args.aovs) {
+        write_aovs(aovs, prefix, format).unwrap_or_else(|e| fail(&e.to_string()));
+    }
+    let write_time = start.elapsed();
+
+    if !args.quiet {
+        let samples = w * h * scene.camera.samples.max(1);
+        eprintln!("scene    {:>10.3}s", load_time.as_secs_f64());
+        eprintln!("render   {:>10.3}s  {}x{}, {} samples per pixel, {} threads",
+            render_time.as_secs_f64(), w, h, scene.camera.samples.max(1), rayon::current_num_threads());
+        if args.denoise {
+            eprintln!("denoise  {:>10.3}s", denoise_time.as_secs_f64());
+        }
+        eprintln!("write    {:>10.3}s  {:?}", write_time.as_secs_f64(), format);
+        eprintln!("         {:>10.0} samples/s", samples as f64 / render_time.as_secs_f64());
+    }
+}
@@ -1,19 +1,27 @@
 use ray_tracer::world::World;
 use ray_tracer::object::{Object, Sphere};
-use ray_tracer::matrix::Matrix;
+use ray_tracer::matrix4::Matrix4;
+use ray_tracer::error::Error;
 use ray_tracer::matrix::TransformBuilder;
 use ray_tracer::color::Color;
 use ray_tracer::color::Canvas;
 use ray_tracer::light::PointLight;
 use ray_tracer::camera::Camera;
 use ray_tracer::tuple::Tuple;
+use ray_tracer::geometry::{Point3, Vector3};
+use ray_tracer::ppm::PpmFormat;
+use ray_tracer::tonemap::{DisplayTransform, ToneMap};
+use ray_tracer::Float;
+use std::io::BufWriter;
 
-const PI: f32 = std::f32::consts::PI;
+const PI: Float = ray_tracer::consts::PI;
 
-fn main() {
+// a zero scale or a bad camera gives an error that names what is wrong
+fn scene(width: Float, height: Float) -> Result<(World, Camera), Error> {
     let floor = {
         let mut obj = Object::new(Sphere::new());
-        obj.apply_transform(Matrix::scale(10.0, 0.01, 10.0));
+        obj.set_name("floor");
+        obj.apply_transform(Matrix4::scale(10.0, 0.01, 10.0))?;
         obj.material.color = Color::new(1.0, 0.9, 0.9);
         obj.material.specular = 0.0;
         obj
@@ -21,6 +29,7 @@ fn main() {
 
     let left_wall = {
         let mut obj = Object::new(Sphere::new());
+        obj.set_name("left wall");
         obj.apply_transform(
             TransformBuilder::identity()
                 .scale(10.0, 0.01, 10.0)
@@ -28,13 +37,14 @@ fn main() {
                 .rotation_y(-PI / 4.0)
                 .translate(0.0, 0.0, 5.0)
                 .build()
-        );
+        )?;
         obj.material = floor.material.clone();
         obj
     };
 
     let right_wall = {
         let mut obj = Object::new(Sphere::new());
+        obj.set_name("right wall");
         obj.apply_transform(
             TransformBuilder::identity()
                 .scale(10.0, 0.01, 10.0)
@@ -42,20 +52,22 @@ fn main() {
                 .rotation_y(PI / 4.0)
                 .translate(0.0, 0.0, 5.0)
                 .build()
-        );
+        )?;
         obj.material = floor.material.clone();
         obj
     };
 
     let middle = {
         let mut obj = Object::new(Sphere::new());
-        obj.apply_transform(Matrix::translate(-0.5, 1.0, 0.5));
+        obj.set_name("middle");
+        obj.apply_transform(Matrix4::translate(-0.5, 1.0, 0.5))?;
         obj
     };
 
     let right = {
         let mut obj = Object::new(Sphere::new());
-        obj.apply_transform(&Matrix::translate(1.5, 0.0, -0.5) * &Matrix::scale(0.5, 0.5, 0.5));
+        obj.set_name("right");
+        obj.apply_transform(Matrix4::translate(1.5, 0.0, -0.5) * Matrix4::scale(0.5, 0.5, 0.5))?;
         obj.material.color = Color::new(0.5, 1.0, 0.1);
         obj.material.diffuse = 0.7;
         obj.material.specular = 0.3;
@@ -64,7 +76,8 @@ fn main() {
     
     let left = {
         let mut obj = Object::new(Sphere::new());
-        obj.apply_transform(&Matrix::translate(-1.5, 0.33, -0.75) * &Matrix::scale(0.33, 0.33, 0.33));
+        obj.set_name("left");
+        obj.apply_transform(Matrix4::translate(-1.5, 0.33, -0.75) * Matrix4::scale(0.33, 0.33, 0.33))?;
         obj.material.color = Color::new(1.0, 0.8, 0.1);
         obj.material.diffuse = 0.7;
         obj.material.specular = 0.3;
@@ -86,18 +99,39 @@ fn main() {
         w
     };
 
+    let camera = {
+        let mut c = Camera::new(width, height, ray_tracer::consts::PI / 3.0);
+        c.set_view(
+            Point3::new(0.0, 1.5, -5.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        )?;
+        c
+    };
+
+    Ok((world, camera))
+}
+
+fn main() {
     let width = 2000.0;
     let height = 1000.0;
+    let (world, camera) = scene(width, height).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        std::process::exit(1)
+    });
 
-    let camera = {
-        let mut c = Camera::new(width, height, std::f32::consts::PI / 3.0);
-        c.inv_transform = Matrix::view_transform(
-            Tuple::new_point(0.0, 1.5, -5.0),
-            Tuple::new_point(0.0, 1.0, 0.0),
-            Tuple::new_vector(0.0, 1.0, 0.0),
-        ).inverse().unwrap();
-        c
+    // linear and clamped like it always was, --aces for the filmic curve and sRGB
+    let display = if std::env::args().any(|a| a == "--aces") {
+        DisplayTransform::srgb(ToneMap::AcesFilmic)
+    } else {
+        DisplayTransform::new()
     };
 
-    println!("{}", Canvas::write_ppm_fn_long(width as usize, height as usize, |px, py| camera.render_pixel(&world, px, py)));
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    Canvas::write_ppm_fn_to(width as usize, height as usize, |px, py| camera.render_pixel(&world, px, py).to_f32(), &mut out, PpmFormat::Ascii, &display)
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            std::process::exit(1)
+        });
 }
@@ -0,0 +1,81 @@
+use crate::geometry::Point3;
+use crate::Float;
+
+// an axis aligned box, empty when min is larger than max
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Bounds {
+    pub fn new(min: Point3, max: Point3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            min: Point3::new(Float::INFINITY, Float::INFINITY, Float::INFINITY),
+            max: Point3::new(-Float::INFINITY, -Float::INFINITY, -Float::INFINITY),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn add_point(&mut self, p: Point3) {
+        self.min = Point3::new(self.min.x.min(p.x), self.min.y.min(p.y), self.min.z.min(p.z));
+        self.max = Point3::new(self.max.x.max(p.x), self.max.y.max(p.y), self.max.z.max(p.z));
+    }
+
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        let mut b = *self;
+        if !other.is_empty() {
+            b.add_point(other.min);
+            b.add_point(other.max);
+        }
+        b
+    }
+
+    pub fn contains(&self, p: Point3) -> bool {
+        (0..3).all(|i| self.min[i] <= p[i] && p[i] <= self.max[i])
+    }
+
+    pub fn corners(&self) -> [Point3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Point3::new(a.x, a.y, a.z),
+            Point3::new(b.x, a.y, a.z),
+            Point3::new(a.x, b.y, a.z),
+            Point3::new(b.x, b.y, a.z),
+            Point3::new(a.x, a.y, b.z),
+            Point3::new(b.x, a.y, b.z),
+            Point3::new(a.x, b.y, b.z),
+            Point3::new(b.x, b.y, b.z),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounds() {
+        let mut b = Bounds::empty();
+        assert!(b.is_empty());
+        assert!(!b.contains(Point3::new(0.0, 0.0, 0.0)));
+
+        b.add_point(Point3::new(1.0, -2.0, 0.0));
+        b.add_point(Point3::new(-1.0, 0.0, 3.0));
+        assert!(!b.is_empty());
+        assert!(b == Bounds::new(Point3::new(-1.0, -2.0, 0.0), Point3::new(1.0, 0.0, 3.0)));
+        assert!(b.contains(Point3::new(0.0, -1.0, 1.5)));
+        assert!(!b.contains(Point3::new(0.0, 1.0, 1.5)));
+
+        let u = b.union(&Bounds::new(Point3::new(0.0, 0.0, 0.0), Point3::new(5.0, 5.0, 5.0)));
+        assert!(u == Bounds::new(Point3::new(-1.0, -2.0, 0.0), Point3::new(5.0, 5.0, 5.0)));
+        assert!(u.union(&Bounds::empty()) == u);
+    }
+}
@@ -1,24 +1,80 @@
-use crate::matrix::Matrix;
-use crate::ray::Ray;
-use crate::tuple::Tuple;
-use crate::world::World;
+use crate::matrix4::Matrix4;
+use crate::transform::Transform;
+use crate::error::Error;
+use crate::ray::{Ray, RayPacket};
+use crate::simd::{F4, Lanes};
+use crate::geometry::{Point3, Vector3};
+use crate::world::{World, Hitinfo};
+use crate::aov::AovSample;
 use crate::color::Color;
+use crate::Float;
+
+const PI: Float = crate::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    Perspective,
+    // the Float is the width of the view in world units
+    Orthographic(Float),
+    // equidistant, the fov is the angle across the shorter side of the image,
+    // black outside the image circle
+    Fisheye,
+    // 360 degrees horizontally, 180 vertically, ignores the fov
+    Equirectangular,
+}
 
 pub struct Camera {
-    hsize: f32,
-    vsize: f32,
-    fov: f32,
-    pub inv_transform: Matrix<f32>,
+    hsize: Float,
+    vsize: Float,
+    fov: Float,
+    // from camera space to world space
+    transform: Transform,
+    // the camera space origin in world space, where every ray of a pinhole,
+    // fisheye and equirectangular camera starts
+    eye: Point3,
+    pub projection: Projection,
 
-    half_width: f32,
-    half_height: f32,
+    // thin lens, an aperture of 0 is a pinhole camera
+    pub aperture_radius: Float,
+    pub focal_distance: Float,
+    pub samples: usize,
 
-    pixel_size: f32,
+    // open and close time of the shutter, objects move from time 0 to 1
+    pub shutter: (Float, Float),
+
+    half_width: Float,
+    half_height: Float,
+
+    pixel_size: Float,
+}
+
+// maps two uniform numbers in [0, 1) to a point on the unit disk
+fn sample_disk(u: Float, v: Float) -> (Float, Float) {
+    let r = u.sqrt();
+    let theta = 2.0 * PI * v;
+    (r * theta.cos(), r * theta.sin())
 }
 
 impl Camera {
-    pub fn new(hsize: f32, vsize: f32, fov: f32) -> Self {
-        let half_view = (fov / 2.0).tan();
+    pub fn new(hsize: Float, vsize: Float, fov: Float) -> Self {
+        let mut c = Self {
+            hsize, vsize, fov,
+            transform: Transform::identity(),
+            eye: Point3::origin(),
+            projection: Projection::Perspective,
+            aperture_radius: 0.0,
+            focal_distance: 1.0,
+            samples: 1,
+            shutter: (0.0, 0.0),
+            half_width: 0.0, half_height: 0.0, pixel_size: 0.0
+        };
+        c.set_size(hsize, vsize);
+        c
+    }
+
+    // keeps the field of view and everything else
+    pub fn set_size(&mut self, hsize: Float, vsize: Float) {
+        let half_view = (self.fov / 2.0).tan();
         let aspect = hsize / vsize;
 
         let (half_width, half_height) = if aspect >= 1.0 {
@@ -27,27 +83,229 @@ impl Camera {
             (half_view * aspect, half_view)
         };
 
-        let pixel_size = (half_width * 2.0) / hsize;
+        self.hsize = hsize;
+        self.vsize = vsize;
+        self.half_width = half_width;
+        self.half_height = half_height;
+        self.pixel_size = (half_width * 2.0) / hsize;
+    }
 
-        Self { hsize, vsize, fov, inv_transform: Matrix::eye(4), half_width, half_height, pixel_size }
+    pub fn width(&self) -> usize {
+        self.hsize as usize
     }
 
-    pub fn ray_for_pixel(&self, px: usize, py: usize) -> Ray {
-        let xoffset = (px as f32 + 0.5) * self.pixel_size;
-        let yoffset = (py as f32 + 0.5) * self.pixel_size;
+    pub fn height(&self) -> usize {
+        self.vsize as usize
+    }
+
+    pub fn fov(&self) -> Float {
+        self.fov
+    }
+
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
 
-        let worldx = self.half_width - xoffset;
-        let worldy = self.half_height - yoffset;
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.eye = transform.point(Point3::origin());
+        self.transform = transform;
+    }
 
-        let pixel = &self.inv_transform * Tuple::new_point(worldx, worldy, -1.0);
-        let origin = &self.inv_transform * Tuple::new_point(0.0, 0.0, 0.0);
+    // places the camera at from, looking at to
+    pub fn set_view(&mut self, from: Point3, to: Point3, up: Vector3) -> Result<(), Error> {
+        if (to - from).magnitude() == 0.0 || up.magnitude() == 0.0 {
+            return Err(Error::CameraView)
+        }
+        let view = Transform::new(Matrix4::view_transform(from.into(), to.into(), up.into())).ok_or(Error::CameraView)?;
+        self.set_transform(view.inverse());
+        Ok(())
+    }
 
-        Ray::new(origin, pixel - origin)
+    // x and y are in pixel units, (px + 0.5, py + 0.5) is the center of a pixel
+    // lens is a point on the unit disk, scaled by the aperture radius,
+    // None where the projection doesn't cover the image, which stays black
+    pub fn ray_for_sample(&self, x: Float, y: Float, lens: (Float, Float)) -> Option<Ray> {
+        let (origin, dir) = match self.projection {
+            Projection::Perspective => self.perspective(x, y, lens),
+            Projection::Orthographic(width) => self.orthographic(x, y, width),
+            Projection::Fisheye => self.fisheye(x, y)?,
+            Projection::Equirectangular => self.equirectangular(x, y),
+        };
+
+        let origin = if origin == Point3::origin() { self.eye } else { self.transform.point(origin) };
+        Some(Ray::new(origin, self.transform.vector(dir)))
     }
 
-    pub fn render_pixel(&self, world: &World, px: usize, py: usize) -> Color<f32>{
-        let r = self.ray_for_pixel(px, py);
-        world.color_at(&r)
+    fn sample_time(&self) -> Float {
+        let (open, close) = self.shutter;
+        if close > open {
+            open + (close - open) * rand::random::<Float>()
+        } else {
+            open
+        }
+    }
+
+    // the following return the origin and direction of a ray in camera space,
+    // the camera looks down -z and +x points to the left of the image
+    fn perspective(&self, x: Float, y: Float, lens: (Float, Float)) -> (Point3, Vector3) {
+        let worldx = self.half_width - x * self.pixel_size;
+        let worldy = self.half_height - y * self.pixel_size;
+
+        // every ray through this pixel converges on the focal plane
+        let focus = Point3::new(worldx * self.focal_distance, worldy * self.focal_distance, -self.focal_distance);
+        let lens = Point3::new(lens.0 * self.aperture_radius, lens.1 * self.aperture_radius, 0.0);
+        (lens, focus - lens)
+    }
+
+    fn orthographic(&self, x: Float, y: Float, width: Float) -> (Point3, Vector3) {
+        let pixel_size = width / self.hsize;
+        let worldx = width / 2.0 - x * pixel_size;
+        let worldy = self.vsize * pixel_size / 2.0 - y * pixel_size;
+        (Point3::new(worldx, worldy, 0.0), Vector3::new(0.0, 0.0, -1.0))
+    }
+
+    // None outside the image circle
+    fn fisheye(&self, x: Float, y: Float) -> Option<(Point3, Vector3)> {
+        // distance from the center, 1.0 on the edge of the shorter side
+        let half = self.hsize.min(self.vsize) / 2.0;
+        let u = (self.hsize / 2.0 - x) / half;
+        let v = (self.vsize / 2.0 - y) / half;
+        let r = (u * u + v * v).sqrt();
+        if r > 1.0 {
+            return None
+        }
+
+        let theta = r * self.fov / 2.0;
+        let dir = if r == 0.0 {
+            Vector3::new(0.0, 0.0, -1.0)
+        } else {
+            let s = theta.sin() / r;
+            Vector3::new(u * s, v * s, -theta.cos())
+        };
+        Some((Point3::origin(), dir))
+    }
+
+    fn equirectangular(&self, x: Float, y: Float) -> (Point3, Vector3) {
+        let lon = (x / self.hsize - 0.5) * 2.0 * PI;
+        let lat = (0.5 - y / self.vsize) * PI;
+        let dir = Vector3::new(-lat.cos() * lon.sin(), lat.sin(), -lat.cos() * lon.cos());
+        (Point3::origin(), dir)
+    }
+
+    // a ray through (x, y) from a random point on the lens, at a random time the shutter is open
+    fn sample_ray(&self, x: Float, y: Float) -> Option<Ray> {
+        let lens = if self.aperture_radius > 0.0 {
+            sample_disk(rand::random(), rand::random())
+        } else {
+            (0.0, 0.0)
+        };
+        let mut r = self.ray_for_sample(x, y, lens)?;
+        r.time = self.sample_time();
+        Some(r)
+    }
+
+    pub fn ray_for_pixel(&self, px: usize, py: usize) -> Option<Ray> {
+        self.sample_ray(px as Float + 0.5, py as Float + 0.5)
+    }
+
+    pub fn render_pixel(&self, world: &World, px: usize, py: usize) -> Color<Float>{
+        if self.samples <= 1 {
+            return self.color_at(world, self.ray_for_pixel(px, py))
+        }
+
+        // jitter inside the pixel, across the lens and the shutter interval
+        let sum = (0..self.samples)
+            .map(|_| {
+                let r = self.sample_ray(px as Float + rand::random::<Float>(), py as Float + rand::random::<Float>());
+                self.color_at(world, r)
+            })
+            .fold(Color::new(0.0, 0.0, 0.0), |a, b| a + b);
+        sum * (1.0 / self.samples as Float)
+    }
+
+    // render_pixel and the passes from the same rays, depth and normal are the
+    // mean over the samples that hit something, albedo over all of them,
+    // and the id is of the first sample that hit
+    pub fn render_pixel_aovs(&self, world: &World, px: usize, py: usize) -> (Color<Float>, AovSample) {
+        let mut color = Color::new(0.0, 0.0, 0.0);
+        let mut aov = AovSample::none();
+        let mut hits = 0;
+        let n = self.samples.max(1);
+        for _ in 0..n {
+            let r = if self.samples <= 1 {
+                self.ray_for_pixel(px, py)
+            } else {
+                self.sample_ray(px as Float + rand::random::<Float>(), py as Float + rand::random::<Float>())
+            };
+            let (c, hit) = match r {
+                Some(r) => world.shade_ray(&r),
+                None => (Color::new(0.0, 0.0, 0.0), None),
+            };
+            color = color + c;
+            if let Some(hi) = hit {
+                let s = self.aov_sample(world, &hi);
+                aov.depth += s.depth;
+                aov.normal = aov.normal + s.normal;
+                aov.albedo = aov.albedo + s.albedo;
+                if aov.id == 0 {
+                    aov.id = s.id;
+                }
+                hits += 1;
+            }
+        }
+
+        let scale = 1.0 / n as Float;
+        if hits > 0 {
+            aov.depth /= hits as Float;
+            aov.normal = aov.normal / hits as Float;
+        }
+        aov.albedo = aov.albedo * scale;
+        (color * scale, aov)
+    }
+
+    fn color_at(&self, world: &World, ray: Option<Ray>) -> Color<Float> {
+        ray.map_or(Color::new(0.0, 0.0, 0.0), |r| world.color_at(&r))
+    }
+
+    fn aov_sample(&self, world: &World, hi: &Hitinfo) -> AovSample {
+        AovSample {
+            // the camera looks down -z
+            depth: -self.transform.inv_point(hi.point).z,
+            normal: hi.normalv.to_vector(),
+            albedo: hi.obj.material.color,
+            id: world.object_index(hi.obj).map_or(0, |i| i + 1),
+        }
+    }
+
+    // like render_pixel for the 2x2 pixels from (px, py) to (px + 1, py + 1), row by row,
+    // each sample is one packet with a ray for every pixel
+    pub fn render_block(&self, world: &World, px: usize, py: usize) -> [Color<Float>; 4] {
+        self.render_block_with::<F4>(world, px, py)
+    }
+
+    // render_block with the packets in L, to compare the kinds of F4
+    pub fn render_block_with<L: Lanes>(&self, world: &World, px: usize, py: usize) -> [Color<Float>; 4] {
+        let pixels = [(px, py), (px + 1, py), (px, py + 1), (px + 1, py + 1)];
+        // a block with a pixel outside the image falls back to single rays
+        let shade = |offset: &dyn Fn() -> Float| {
+            let rays = pixels.map(|(x, y)| self.sample_ray(x as Float + offset(), y as Float + offset()));
+            match rays {
+                [Some(a), Some(b), Some(c), Some(d)] => world.color_at_packet(&RayPacket::<L>::new(&[a, b, c, d])),
+                _ => rays.map(|r| self.color_at(world, r)),
+            }
+        };
+        if self.samples <= 1 {
+            return shade(&|| 0.5)
+        }
+
+        let mut sum = [Color::new(0.0, 0.0, 0.0); 4];
+        for _ in 0..self.samples {
+            let colors = shade(&|| rand::random::<Float>());
+            for (s, c) in sum.iter_mut().zip(colors.iter()) {
+                *s = *s + *c;
+            }
+        }
+        sum.map(|s| s * (1.0 / self.samples as Float))
     }
 }
 
@@ -58,14 +316,118 @@ mod test {
     #[test]
     fn test_ray() {
         let w = World::new_default();
-        let mut c = Camera::new(11.0, 11.0, std::f32::consts::PI / 2.0);
+        let mut c = Camera::new(11.0, 11.0, crate::consts::PI / 2.0);
 
-        let from = Tuple::new_point(0.0, 0.0, -5.0);
-        let to = Tuple::new_point(0.0, 0.0, 0.0);
-        let up = Tuple::new_vector(0.0, 1.0, 0.0);
+        let from = Point3::new(0.0, 0.0, -5.0);
+        let to = Point3::origin();
+        let up = Vector3::new(0.0, 1.0, 0.0);
 
-        c.inv_transform = Matrix::view_transform(from, to, up).inverse().unwrap();
+        c.set_view(from, to, up).unwrap();
         println!("{:?}", c.render_pixel(&w, 5, 5));
         assert!(c.render_pixel(&w, 5, 5) == Color::new(0.38066125, 0.4758265, 0.28549594));
     }
+
+    #[test]
+    fn test_aovs() {
+        let w = World::new_default();
+        let mut c = Camera::new(11.0, 11.0, crate::consts::PI / 2.0);
+        c.set_view(Point3::new(0.0, 0.0, -5.0), Point3::origin(), Vector3::new(0.0, 1.0, 0.0)).unwrap();
+
+        let (color, aov) = c.render_pixel_aovs(&w, 5, 5);
+        assert!(color == c.render_pixel(&w, 5, 5));
+        assert!((aov.depth - 4.0).abs() < 1e-5);
+        assert!(aov.normal.eq_real(&Vector3::new(0.0, 0.0, -1.0)));
+        assert!(aov.albedo == w.objects()[0].material.color);
+        assert!(aov.id == 1);
+
+        let (color, aov) = c.render_pixel_aovs(&w, 0, 0);
+        assert!(color == Color::new(0.0, 0.0, 0.0) && aov == AovSample::none());
+    }
+
+    #[test]
+    fn test_thin_lens() {
+        let mut c = Camera::new(201.0, 101.0, crate::consts::PI / 2.0);
+        c.aperture_radius = 0.5;
+        c.focal_distance = 4.0;
+
+        // rays leaving from different points of the lens meet on the focal plane
+        let r1 = c.ray_for_sample(20.5, 30.5, (0.0, 0.0)).unwrap();
+        let r2 = c.ray_for_sample(20.5, 30.5, (1.0, 0.0)).unwrap();
+        let r3 = c.ray_for_sample(20.5, 30.5, (0.0, -1.0)).unwrap();
+        assert!(r2.origin == Point3::new(0.5, 0.0, 0.0));
+
+        let t1 = -c.focal_distance / r1.dir.z;
+        let t2 = -c.focal_distance / r2.dir.z;
+        let t3 = -c.focal_distance / r3.dir.z;
+        let p1 = r1.pos(t1);
+        assert!((p1 - r2.pos(t2)).magnitude() < 1e-4);
+        assert!((p1 - r3.pos(t3)).magnitude() < 1e-4);
+
+        // without an aperture the sample on the lens is ignored
+        c.aperture_radius = 0.0;
+        let r = c.ray_for_pixel(100, 50).unwrap();
+        assert!(r.origin == Point3::origin());
+        assert!(r.dir.eq_real(&Vector3::new(0.0, 0.0, -1.0)));
+    }
+
+    #[test]
+    fn test_projections() {
+        let mut c = Camera::new(200.0, 100.0, PI);
+
+        c.projection = Projection::Orthographic(4.0);
+        let r1 = c.ray_for_pixel(0, 0).unwrap();
+        let r2 = c.ray_for_pixel(199, 99).unwrap();
+        assert!(r1.dir == r2.dir);
+        assert!(r1.origin.eq_real(&Point3::new(1.99, 0.99, 0.0)));
+        assert!(r2.origin.eq_real(&Point3::new(-1.99, -0.99, 0.0)));
+
+        // a 180 degree fisheye sees sideways on the edge of the image circle
+        c.projection = Projection::Fisheye;
+        let r = c.ray_for_sample(100.0, 50.0, (0.0, 0.0)).unwrap();
+        assert!(r.dir == Vector3::new(0.0, 0.0, -1.0));
+        let r = c.ray_for_sample(50.0, 50.0, (0.0, 0.0)).unwrap();
+        assert!(r.dir.eq_real(&Vector3::new(1.0, 0.0, 0.0)));
+
+        // and nothing outside of it, the corners stay black
+        assert!(c.ray_for_sample(49.0, 50.0, (0.0, 0.0)).is_none());
+        assert!(c.ray_for_pixel(0, 0).is_none());
+        let w = World::new_default();
+        assert!(c.render_pixel(&w, 100, 50) != Color::new(0.0, 0.0, 0.0));
+        assert!(c.render_pixel(&w, 0, 0) == Color::new(0.0, 0.0, 0.0));
+        assert!(c.render_block(&w, 0, 0)[0] == Color::new(0.0, 0.0, 0.0));
+
+        c.projection = Projection::Equirectangular;
+        let r = c.ray_for_sample(100.0, 50.0, (0.0, 0.0)).unwrap();
+        assert!(r.dir.eq_real(&Vector3::new(0.0, 0.0, -1.0)));
+        let r = c.ray_for_sample(150.0, 50.0, (0.0, 0.0)).unwrap();
+        assert!(r.dir.eq_real(&Vector3::new(-1.0, 0.0, 0.0)));
+        let r = c.ray_for_sample(100.0, 0.0, (0.0, 0.0)).unwrap();
+        assert!(r.dir.eq_real(&Vector3::new(0.0, 1.0, 0.0)));
+
+        // projections keep working through the camera transform
+        c.set_transform(Transform::new(Matrix4::translate(0.0, 2.0, 0.0)).unwrap());
+        let r = c.ray_for_sample(100.0, 50.0, (0.0, 0.0)).unwrap();
+        assert!(r.origin == Point3::new(0.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn test_view() {
+        let mut c = Camera::new(10.0, 10.0, PI / 2.0);
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        c.set_view(Point3::origin(), Point3::new(0.0, 0.0, 1.0), up).unwrap();
+        assert!(c.ray_for_sample(5.0, 5.0, (0.0, 0.0)).unwrap().dir == Vector3::new(0.0, 0.0, 1.0));
+
+        // every pinhole and fisheye ray starts at the eye
+        let from = Point3::new(1.0, 2.0, -5.0);
+        c.set_view(from, Point3::origin(), up).unwrap();
+        c.projection = Projection::Fisheye;
+        assert!((c.ray_for_sample(1.0, 2.0, (0.0, 0.0)).unwrap().origin - from).magnitude() < 1e-5);
+        c.projection = Projection::Perspective;
+        assert!((c.ray_for_sample(7.0, 3.0, (0.0, 0.0)).unwrap().origin - from).magnitude() < 1e-5);
+
+        let p = Point3::new(0.0, 1.0, 0.0);
+        assert!(c.set_view(p, p, up) == Err(Error::CameraView));
+        assert!(c.set_view(p, Point3::new(0.0, 3.0, 0.0), up) == Err(Error::CameraView));
+        assert!(c.set_view(p, Point3::origin(), p - p) == Err(Error::CameraView));
+    }
 }
@@ -1,12 +1,11 @@
 use std::ops;
-use itertools::Itertools;
-use rayon::prelude::*;
 use num_traits::Num;
 use num_traits::identities::{One, Zero};
 use num_traits::real::Real;
-
-const SCALING_FACTOR: u32 = 255;
-const PPM_LINE_LEN: usize = 70;
+use crate::ppm::PpmFormat;
+use crate::tonemap::DisplayTransform;
+use crate::simd::F4;
+use crate::Float;
 
 #[inline]
 fn clamp(x: f32, min: f32, max: f32) -> f32 {
@@ -19,6 +18,12 @@ fn clamp(x: f32, min: f32, max: f32) -> f32 {
     }
 }
 
+// scales a color to integers in 0..=max, rounding to the nearest one
+pub(crate) fn quantize(c: Color<f32>, max: u32) -> Color<u32> {
+    (c * (max as f32))
+        .fmap(|x| (clamp(x, 0.0, max as f32) + 0.5) as u32) // the 0.5 is for rounding
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Color<T>(pub T, pub T, pub T);
 
@@ -35,6 +40,34 @@ impl<A: Copy> Color<A> {
     }
 }
 
+// the Canvas always stores f32, whatever precision the renderer uses
+impl Color<f32> {
+    pub fn to_f32(self) -> Color<f32> {
+        self
+    }
+}
+
+impl Color<f64> {
+    pub fn to_f32(self) -> Color<f32> {
+        self.fmap(|x| x as f32)
+    }
+}
+
+// r, g and b in the first three lanes and 0 in the last, so a chain of
+// operations can be done on all channels at once, with SSE for f32
+impl Color<Float> {
+    #[inline]
+    pub fn lanes(self) -> F4 {
+        F4::new(self.0, self.1, self.2, 0.0)
+    }
+
+    #[inline]
+    pub fn from_lanes(v: F4) -> Self {
+        let [r, g, b, _] = v.to_array();
+        Color(r, g, b)
+    }
+}
+
 impl<T: Num + Real> PartialEq for Color<T> {
     fn eq(&self, other: &Color<T>) -> bool {
         (self.0 - other.0).abs() <= T::epsilon() && 
@@ -128,70 +161,25 @@ impl Canvas {
         self.colors[self.get_index(x, y)]
     }
 
+    // one pixel per line
     pub fn write_ppm_fn_long<F>(w: usize, h: usize, f: F) -> String 
     where F: Fn(usize, usize) -> Color<f32> + std::marker::Sync {
-        let header = format!("P3 {} {} {}", w, h, SCALING_FACTOR);
-        let content = (0..h).into_par_iter().map(|y| { (0..w).into_par_iter().map(|x| {
-                let c = (f(x, y)  * (SCALING_FACTOR as f32))
-                    .fmap(|x| clamp(x, 0.0, SCALING_FACTOR as f32) + 0.5); // the 0.5 is for rounding
-                let mut res = String::new();
-                res.push_str(&(c.0 as u32).to_string());
-                res.push_str(" ");
-                res.push_str(&(c.1 as u32).to_string());
-                res.push_str(" ");
-                res.push_str(&(c.2 as u32).to_string());
-                res.push_str("\n");
-                res
-            }).collect::<Vec<String>>().concat() }).collect::<Vec<String>>().concat();
-
-        // we need a trailing newline, some programs (like imagemagick) need that in order to work
-        format!("{}\n{}\n", header, content)
+        let mut out = Vec::new();
+        Self::write_ppm_fn_long_to(w, h, f, &mut out, &DisplayTransform::new()).unwrap(); // writing to a Vec can't fail
+        String::from_utf8(out).unwrap()
     }
 
     pub fn write_ppm_fn<F>(w: usize, h: usize, f: F) -> String 
     where F: Fn(usize, usize) -> Color<f32> + std::marker::Sync {
-        let header = format!("P3 {} {} {}", w, h, SCALING_FACTOR);
-        let content = (0..h).into_par_iter().map(|y| { (0..w).into_par_iter().map(|x| {
-                let c = (f(x, y)  * (SCALING_FACTOR as f32))
-                    .fmap(|x| clamp(x, 0.0, SCALING_FACTOR as f32) + 0.5); // the 0.5 is for rounding
-                [(c.0 as u32).to_string(), (c.1 as u32).to_string(), (c.2 as u32).to_string()]
-            }).collect::<Vec<[String; 3]>>() }).collect::<Vec<Vec<[String; 3]>>>().concat().concat().iter()
-            .fold((0, String::from("")), |(line_len, all_str), s| {
-                if all_str.len() == 0 {
-                    (s.len(), s.to_string())
-                } else {
-                    if line_len + s.len() + 1 >= PPM_LINE_LEN {
-                        (s.len(), format!("{}\n{}", all_str, s))
-                    } else {
-                        (line_len + s.len() + 1, format!("{} {}", all_str, s))
-                    }
-                }
-            }).1;
-        // we need a trailing newline, some programs (like imagemagick) need that in order to work
-        format!("{}\n{}\n", header, content)
+        let mut out = Vec::new();
+        Self::write_ppm_fn_to(w, h, f, &mut out, PpmFormat::Ascii, &DisplayTransform::new()).unwrap(); // writing to a Vec can't fail
+        String::from_utf8(out).unwrap()
     }
 
     pub fn write_ppm(&self) -> String {
-        let header = format!("P3 {} {} {}", self.w, self.h, SCALING_FACTOR);
-        let content = (0..self.h).cartesian_product(0..self.w)
-            .map(|(y, x)| {
-                let c = (self.pixel_at(x, y) * (SCALING_FACTOR as f32))
-                    .fmap(|x| clamp(x, 0.0, SCALING_FACTOR as f32) + 0.5); // the 0.5 is for rounding
-                [format!("{}", c.0 as u32), format!("{}", c.1 as u32), format!("{}", c.2 as u32)]
-            }).collect::<Vec<[String; 3]>>().concat().iter()
-            .fold((0, String::from("")), |(line_len, all_str), s| {
-                if all_str.len() == 0 {
-                    (s.len(), s.to_string())
-                } else {
-                    if line_len + s.len() + 1 >= PPM_LINE_LEN {
-                        (s.len(), format!("{}\n{}", all_str, s))
-                    } else {
-                        (line_len + s.len() + 1, format!("{} {}", all_str, s))
-                    }
-                }
-            }).1;
-        // we need a trailing newline, some programs (like imagemagick) need that in order to work
-        format!("{}\n{}\n", header, content)
+        let mut out = Vec::new();
+        self.write_ppm_to(&mut out, PpmFormat::Ascii, &DisplayTransform::new()).unwrap(); // writing to a Vec can't fail
+        String::from_utf8(out).unwrap()
     }
 }
 
@@ -200,6 +188,8 @@ fn test() {
     let c1 = Color(1.0, 0.2, 0.4);
     let c2 = Color(0.9, 1.0, 0.1);
     assert!(c1 * c2 == Color(0.9, 0.2, 0.04));
+    assert!(Color::from_lanes(c1.lanes() * c2.lanes() + c1.lanes()) == c1 * c2 + c1);
+    assert!(c1.lanes().to_array()[3] == 0.0);
 
     let mut canvas = Canvas::new(10, 20);
     let red = Color(1.0, 0.0, 0.0);
@@ -0,0 +1,170 @@
+// edge-avoiding à-trous wavelet filter, guided by the albedo and normal passes,
+// it blurs noise away but stops at edges between objects and where the surface turns
+use rayon::prelude::*;
+use crate::aov::Aovs;
+use crate::color::{Canvas, Color};
+
+pub struct DenoiseOptions {
+    // every iteration doubles the spacing of the 5x5 kernel, after 5 it reaches 62 pixels away
+    pub iterations: usize,
+    // how different colors, normals and albedos of neighbours can be before
+    // they stop counting, smaller keeps more detail and more noise
+    pub sigma_color: f32,
+    pub sigma_normal: f32,
+    pub sigma_albedo: f32,
+}
+
+impl DenoiseOptions {
+    pub fn new() -> Self {
+        Self { iterations: 5, sigma_color: 0.5, sigma_normal: 0.2, sigma_albedo: 0.1 }
+    }
+}
+
+impl Default for DenoiseOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// B3 spline
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+// below this the color isn't divided by the albedo, like the background
+const MIN_ALBEDO: f32 = 1e-3;
+
+fn distance2(a: Color<f32>, b: Color<f32>) -> f32 {
+    let d = a - b;
+    d.0 * d.0 + d.1 * d.1 + d.2 * d.2
+}
+
+fn channels(c: Color<f32>, albedo: Color<f32>, f: impl Fn(f32, f32) -> f32) -> Color<f32> {
+    let pick = |v: f32, a: f32| if a > MIN_ALBEDO { f(v, a) } else { v };
+    Color::new(pick(c.0, albedo.0), pick(c.1, albedo.1), pick(c.2, albedo.2))
+}
+
+// the same result on every run and any number of threads, there is no randomness
+// and every pixel only reads the previous iteration
+pub fn denoise(color: &Canvas, aovs: &Aovs, options: &DenoiseOptions) -> Canvas {
+    let (w, h) = (color.w, color.h);
+    let pixels = |c: &Canvas| (0..w * h).map(|i| c.pixel_at(i % w, i / w)).collect::<Vec<_>>();
+    let albedo = pixels(&aovs.albedo);
+    let normal = pixels(&aovs.normal);
+
+    // filtered without the albedo so the texture of surfaces isn't blurred
+    let mut current = pixels(color).into_iter().zip(albedo.iter())
+        .map(|(c, &a)| channels(c, a, |v, a| v / a))
+        .collect::<Vec<_>>();
+
+    // once the spacing is as large as the image every neighbour is outside of it
+    // and the pixel only keeps itself, so more iterations wouldn't change anything
+    let steps = (0..options.iterations)
+        .map(|i| 1_usize << i)
+        .take_while(|&step| step < w.max(h));
+    for step in steps {
+        // finer detail is left at larger spacings
+        let sigma_color = options.sigma_color / step as f32;
+        let (wc, wn, wa) = (
+            1.0 / (sigma_color * sigma_color),
+            1.0 / (options.sigma_normal * options.sigma_normal),
+            1.0 / (options.sigma_albedo * options.sigma_albedo),
+        );
+        let previous = &current;
+        current = (0..w * h).into_par_iter()
+            .map(|p| {
+                let (x, y) = ((p % w) as isize, (p / w) as isize);
+                let mut sum = Color::new(0.0, 0.0, 0.0);
+                let mut total = 0.0;
+                for (ky, hy) in KERNEL.iter().enumerate() {
+                    let qy = y + (ky as isize - 2) * step as isize;
+                    if qy < 0 || qy >= h as isize {
+                        continue
+                    }
+                    for (kx, hx) in KERNEL.iter().enumerate() {
+                        let qx = x + (kx as isize - 2) * step as isize;
+                        if qx < 0 || qx >= w as isize {
+                            continue
+                        }
+                        let q = qy as usize * w + qx as usize;
+                        let weight = hx * hy * (
+                            -distance2(previous[p], previous[q]) * wc
+                            - distance2(normal[p], normal[q]) * wn
+                            - distance2(albedo[p], albedo[q]) * wa
+                        ).exp();
+                        sum = sum + previous[q] * weight;
+                        total += weight;
+                    }
+                }
+                // the pixel itself always counts, so total is never 0
+                sum * (1.0 / total)
+            })
+            .collect();
+    }
+
+    Canvas::new_fn(w, h, |x, y| channels(current[y * w + x], albedo[y * w + x], |v, a| v * a))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // noise on two halves that face different ways
+    fn noisy(w: usize, h: usize) -> (Canvas, Aovs) {
+        let mut aovs = Aovs::new(w, h);
+        let mut color = Canvas::new(w, h);
+        let mut seed = 12345_u32;
+        for y in 0..h {
+            for x in 0..w {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let noise = (seed >> 16) as f32 / 65536.0 - 0.5;
+                let left = x < w / 2;
+                let base = if left { 0.2 } else { 0.8 };
+                color.write_pixel(x, y, Color::new(base + noise * 0.2, base + noise * 0.2, base));
+                aovs.albedo.write_pixel(x, y, Color::new(1.0, 1.0, 1.0));
+                aovs.normal.write_pixel(x, y, if left { Color::new(1.0, 0.0, 0.0) } else { Color::new(0.0, 0.0, -1.0) });
+            }
+        }
+        (color, aovs)
+    }
+
+    fn error(c: &Canvas, x0: usize, x1: usize, expected: f32) -> f32 {
+        let mut sum = 0.0;
+        for y in 0..c.h {
+            for x in x0..x1 {
+                sum += (c.pixel_at(x, y).0 - expected).abs();
+            }
+        }
+        sum / ((x1 - x0) * c.h) as f32
+    }
+
+    #[test]
+    fn test_denoise() {
+        let (color, aovs) = noisy(32, 16);
+        let options = DenoiseOptions::new();
+        let out = denoise(&color, &aovs, &options);
+
+        // less noise on both sides, and the edge between them stays sharp
+        assert!(error(&out, 0, 16, 0.2) < error(&color, 0, 16, 0.2) / 3.0);
+        assert!(error(&out, 16, 32, 0.8) < error(&color, 16, 32, 0.8) / 3.0);
+        assert!((out.pixel_at(15, 8).0 - 0.2).abs() < 0.05);
+        assert!((out.pixel_at(16, 8).0 - 0.8).abs() < 0.05);
+        assert!((out.pixel_at(3, 3).2 - 0.2).abs() < 1e-5);
+
+        let again = denoise(&color, &aovs, &options);
+        for y in 0..16 {
+            for x in 0..32 {
+                let (a, b) = (again.pixel_at(x, y), out.pixel_at(x, y));
+                assert!((a.0, a.1, a.2) == (b.0, b.1, b.2));
+            }
+        }
+
+        let mut options = DenoiseOptions::new();
+        options.iterations = 0;
+        assert!(denoise(&color, &aovs, &options).pixel_at(7, 9) == color.pixel_at(7, 9));
+
+        // spacings past the size of the image are skipped, however many are asked for
+        options.iterations = 200;
+        let many = denoise(&color, &aovs, &options);
+        let five = denoise(&color, &aovs, &DenoiseOptions::default());
+        assert!(many.pixel_at(20, 4) == five.pixel_at(20, 4));
+    }
+}
@@ -0,0 +1,36 @@
+use std::fmt;
+use crate::scene::SceneError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    // the transform of the named object has no inverse
+    SingularTransform(String),
+    // the object would pass through a transform without an inverse while moving
+    SingularMotion(String),
+    // an end of the motion isn't translate * rotate * scale, like with a shear,
+    // so it can't be interpolated without changing what the object looks like
+    UnsupportedMotion(String),
+    // from and to are the same point, or the camera looks along its up vector
+    CameraView,
+    Scene(SceneError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::SingularTransform(obj) => write!(f, "the transform of {} can't be inverted", obj),
+            Error::SingularMotion(obj) => write!(f, "the motion of {} goes through a transform that can't be inverted", obj),
+            Error::UnsupportedMotion(obj) => write!(f, "the motion of {} starts or ends with a shear or a scale after a rotation", obj),
+            Error::CameraView => write!(f, "the camera's from, to and up don't give a view direction"),
+            Error::Scene(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<SceneError> for Error {
+    fn from(e: SceneError) -> Self {
+        Error::Scene(e)
+    }
+}
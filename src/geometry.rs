@@ -0,0 +1,322 @@
+// points, vectors and normals as their own types, so mixing them up doesn't compile,
+// Tuple tells them apart only by w at runtime
+use std::convert::TryFrom;
+use std::ops;
+use crate::matrix4::Matrix4;
+use crate::tuple::Tuple;
+use crate::simd::F4;
+use crate::Float;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point3 {
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vector3 {
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+// a unit vector perpendicular to a surface, it only changes through Transform::normal
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Normal3 {
+    x: Float,
+    y: Float,
+    z: Float,
+}
+
+fn close(a: (Float, Float, Float), b: (Float, Float, Float)) -> bool {
+    (a.0 - b.0).abs() <= Float::EPSILON && (a.1 - b.1).abs() <= Float::EPSILON && (a.2 - b.2).abs() <= Float::EPSILON
+}
+
+impl Point3 {
+    pub fn new(x: Float, y: Float, z: Float) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn origin() -> Self {
+        Self::new(0.0, 0.0, 0.0)
+    }
+
+    pub fn eq_real(&self, other: &Point3) -> bool {
+        close((self.x, self.y, self.z), (other.x, other.y, other.z))
+    }
+}
+
+impl Vector3 {
+    pub fn new(x: Float, y: Float, z: Float) -> Self {
+        Self { x, y, z }
+    }
+
+    fn lanes(&self) -> F4 {
+        F4::new(self.x, self.y, self.z, 0.0)
+    }
+
+    fn from_lanes(v: F4) -> Vector3 {
+        let [x, y, z, _] = v.to_array();
+        Vector3::new(x, y, z)
+    }
+
+    // dot and cross don't use F4, moving the fields into a register and
+    // back takes longer than the products, see benches/sphere_scene.rs
+    pub fn dot(&self, other: Vector3) -> Float {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(&self, other: Vector3) -> Vector3 {
+        Vector3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn magnitude(&self) -> Float {
+        self.dot(*self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Vector3 {
+        *self / self.magnitude()
+    }
+
+    // mirrors the vector around n, like light bouncing off a surface
+    pub fn reflect(&self, n: Normal3) -> Vector3 {
+        *self - n * (2.0 * n.dot(*self))
+    }
+
+    pub fn eq_real(&self, other: &Vector3) -> bool {
+        close((self.x, self.y, self.z), (other.x, other.y, other.z))
+    }
+}
+
+impl Normal3 {
+    // normalizes v
+    pub fn new(v: Vector3) -> Self {
+        let v = v.normalize();
+        Self { x: v.x, y: v.y, z: v.z }
+    }
+
+    pub fn x(&self) -> Float { self.x }
+    pub fn y(&self) -> Float { self.y }
+    pub fn z(&self) -> Float { self.z }
+
+    pub fn dot(&self, v: Vector3) -> Float {
+        self.to_vector().dot(v)
+    }
+
+    pub fn to_vector(&self) -> Vector3 {
+        Vector3::new(self.x, self.y, self.z)
+    }
+
+    pub fn eq_real(&self, other: &Normal3) -> bool {
+        close((self.x, self.y, self.z), (other.x, other.y, other.z))
+    }
+}
+
+impl ops::Sub for Point3 {
+    type Output = Vector3;
+
+    fn sub(self, other: Point3) -> Vector3 {
+        Vector3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl ops::Add<Vector3> for Point3 {
+    type Output = Point3;
+
+    fn add(self, v: Vector3) -> Point3 {
+        Point3::new(self.x + v.x, self.y + v.y, self.z + v.z)
+    }
+}
+
+impl ops::Sub<Vector3> for Point3 {
+    type Output = Point3;
+
+    fn sub(self, v: Vector3) -> Point3 {
+        Point3::new(self.x - v.x, self.y - v.y, self.z - v.z)
+    }
+}
+
+impl ops::Add for Vector3 {
+    type Output = Vector3;
+
+    fn add(self, other: Vector3) -> Vector3 {
+        Vector3::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl ops::Sub for Vector3 {
+    type Output = Vector3;
+
+    fn sub(self, other: Vector3) -> Vector3 {
+        Vector3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl ops::Neg for Vector3 {
+    type Output = Vector3;
+
+    fn neg(self) -> Vector3 {
+        Vector3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl ops::Mul<Float> for Vector3 {
+    type Output = Vector3;
+
+    fn mul(self, f: Float) -> Vector3 {
+        Vector3::new(self.x * f, self.y * f, self.z * f)
+    }
+}
+
+impl ops::Div<Float> for Vector3 {
+    type Output = Vector3;
+
+    fn div(self, f: Float) -> Vector3 {
+        Vector3::new(self.x / f, self.y / f, self.z / f)
+    }
+}
+
+impl ops::Neg for Normal3 {
+    type Output = Normal3;
+
+    fn neg(self) -> Normal3 {
+        Normal3 { x: -self.x, y: -self.y, z: -self.z }
+    }
+}
+
+// scaling a normal gives an offset along it, which is no longer a normal
+impl ops::Mul<Float> for Normal3 {
+    type Output = Vector3;
+
+    fn mul(self, f: Float) -> Vector3 {
+        Vector3::new(self.x * f, self.y * f, self.z * f)
+    }
+}
+
+impl ops::Index<usize> for Point3 {
+    type Output = Float;
+
+    fn index(&self, i: usize) -> &Float {
+        match i {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("index {} out of range for a Point3", i),
+        }
+    }
+}
+
+impl ops::Index<usize> for Vector3 {
+    type Output = Float;
+
+    fn index(&self, i: usize) -> &Float {
+        match i {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("index {} out of range for a Vector3", i),
+        }
+    }
+}
+
+impl ops::Mul<Point3> for Matrix4<Float> {
+    type Output = Point3;
+
+    #[inline]
+    fn mul(self, p: Point3) -> Point3 {
+        let [x, y, z, _] = F4::mul_matrix(self.as_array(), F4::new(p.x, p.y, p.z, 1.0)).to_array();
+        Point3::new(x, y, z)
+    }
+}
+
+impl ops::Mul<Vector3> for Matrix4<Float> {
+    type Output = Vector3;
+
+    #[inline]
+    fn mul(self, v: Vector3) -> Vector3 {
+        Vector3::from_lanes(F4::mul_matrix(self.as_array(), v.lanes()))
+    }
+}
+
+impl From<Point3> for Tuple<Float> {
+    fn from(p: Point3) -> Self {
+        Tuple::new_point(p.x, p.y, p.z)
+    }
+}
+
+impl From<Vector3> for Tuple<Float> {
+    fn from(v: Vector3) -> Self {
+        Tuple::new_vector(v.x, v.y, v.z)
+    }
+}
+
+impl From<Normal3> for Tuple<Float> {
+    fn from(n: Normal3) -> Self {
+        Tuple::new_vector(n.x, n.y, n.z)
+    }
+}
+
+// the tuple comes back as the error when its w is wrong
+impl TryFrom<Tuple<Float>> for Point3 {
+    type Error = Tuple<Float>;
+
+    fn try_from(t: Tuple<Float>) -> Result<Self, Self::Error> {
+        if t.3 == 1.0 { Ok(Point3::new(t.0, t.1, t.2)) } else { Err(t) }
+    }
+}
+
+impl TryFrom<Tuple<Float>> for Vector3 {
+    type Error = Tuple<Float>;
+
+    fn try_from(t: Tuple<Float>) -> Result<Self, Self::Error> {
+        if t.3 == 0.0 { Ok(Vector3::new(t.0, t.1, t.2)) } else { Err(t) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ops() {
+        let p = Point3::new(3.0, 2.0, 1.0);
+        let q = Point3::new(5.0, 6.0, 7.0);
+        assert!(p - q == Vector3::new(-2.0, -4.0, -6.0));
+        assert!(p + Vector3::new(1.0, 1.0, 1.0) == Point3::new(4.0, 3.0, 2.0));
+        assert!(p - Vector3::new(1.0, 1.0, 1.0) == Point3::new(2.0, 1.0, 0.0));
+
+        let a = Vector3::new(1.0, 2.0, 3.0);
+        let b = Vector3::new(2.0, 3.0, 4.0);
+        assert!(a.dot(b) == 20.0);
+        assert!(a.cross(b) == Vector3::new(-1.0, 2.0, -1.0));
+        assert!(Vector3::new(0.0, 3.0, 4.0).normalize() == Vector3::new(0.0, 0.6, 0.8));
+
+        let n = Normal3::new(Vector3::new(0.0, 2.0, 0.0));
+        assert!(n.to_vector() == Vector3::new(0.0, 1.0, 0.0));
+        assert!(Vector3::new(1.0, -1.0, 0.0).reflect(n) == Vector3::new(1.0, 1.0, 0.0));
+        assert!(p + n * 2.0 == Point3::new(3.0, 4.0, 1.0));
+    }
+
+    #[test]
+    fn test_matrix() {
+        let m = Matrix4::translate(1.0, 2.0, 3.0) * Matrix4::scale(2.0, 2.0, 2.0);
+        assert!(m * Point3::new(1.0, 1.0, 1.0) == Point3::new(3.0, 4.0, 5.0));
+        assert!(m * Vector3::new(1.0, 1.0, 1.0) == Vector3::new(2.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn test_tuple() {
+        let t: Tuple<Float> = Point3::new(1.0, 2.0, 3.0).into();
+        assert!(t == Tuple::new_point(1.0, 2.0, 3.0));
+        assert!(Point3::try_from(t) == Ok(Point3::new(1.0, 2.0, 3.0)));
+        assert!(Vector3::try_from(t) == Err(t));
+
+        let t: Tuple<Float> = Normal3::new(Vector3::new(0.0, 0.0, -3.0)).into();
+        assert!(Vector3::try_from(t) == Ok(Vector3::new(0.0, 0.0, -1.0)));
+    }
+}
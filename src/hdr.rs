@@ -0,0 +1,156 @@
+use std::io;
+use std::io::Write;
+use crate::color::{Canvas, Color};
+
+// the brightest channel rgbe can store, a mantissa of 255 with the largest exponent
+const MAX_RGBE: f32 = 255.0 * (1u128 << 119) as f32;
+
+// shared exponent encoding, keeps 8 bits of mantissa for the brightest channel,
+// larger values and infinity are written as the largest there is, NaN as 0
+fn rgbe(c: Color<f32>) -> [u8; 4] {
+    let c = c.fmap(|x| if x.is_nan() { 0.0 } else { x.clamp(0.0, MAX_RGBE) });
+    let v = c.0.max(c.1).max(c.2);
+    if v < 1e-32 {
+        return [0, 0, 0, 0]
+    }
+
+    // v = m * 2^e with m in [0.5, 1)
+    let mut e = v.log2().floor() as i32 + 1;
+    let mut m = v / 2f32.powi(e);
+    if m >= 1.0 {
+        m /= 2.0;
+        e += 1;
+    }
+    let scale = m * 256.0 / v;
+    [(c.0 * scale) as u8, (c.1 * scale) as u8, (c.2 * scale) as u8, (e + 128) as u8]
+}
+
+// one channel of a scanline, in runs of equal bytes and literal dumps of at most 128
+fn rle(data: &[u8], out: &mut Vec<u8>) {
+    const MIN_RUN: usize = 4;
+    let mut i = 0;
+    while i < data.len() {
+        // find the next run long enough to be worth it
+        let mut run_start = i;
+        let mut run_len = 0;
+        while run_start < data.len() {
+            run_len = data[run_start..].iter().take(127).take_while(|&&b| b == data[run_start]).count();
+            if run_len >= MIN_RUN {
+                break
+            }
+            run_start += run_len;
+        }
+        if run_len < MIN_RUN {
+            run_start = data.len();
+        }
+
+        while i < run_start {
+            let n = (run_start - i).min(128);
+            out.push(n as u8);
+            out.extend_from_slice(&data[i..i + n]);
+            i += n;
+        }
+
+        if run_start < data.len() {
+            out.push(128 + run_len as u8);
+            out.push(data[run_start]);
+            i = run_start + run_len;
+        }
+    }
+}
+
+impl Canvas {
+    // Radiance RGBE, with the usual per channel run length encoding
+    pub fn write_hdr_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", self.h, self.w)?;
+
+        let mut line = Vec::with_capacity(self.w * 4);
+        let mut channels: Vec<Vec<u8>> = (0..4).map(|_| Vec::with_capacity(self.w)).collect();
+        for y in 0..self.h {
+            line.clear();
+            if self.w < 8 || self.w > 0x7fff {
+                // too narrow or too wide for run length encoding, write it flat
+                for x in 0..self.w {
+                    line.extend_from_slice(&rgbe(self.pixel_at(x, y)));
+                }
+            } else {
+                for ch in channels.iter_mut() {
+                    ch.clear();
+                }
+                for x in 0..self.w {
+                    let p = rgbe(self.pixel_at(x, y));
+                    for (ch, &b) in channels.iter_mut().zip(p.iter()) {
+                        ch.push(b);
+                    }
+                }
+                line.extend_from_slice(&[2, 2, (self.w >> 8) as u8, (self.w & 0xff) as u8]);
+                for ch in channels.iter() {
+                    rle(ch, &mut line);
+                }
+            }
+            out.write_all(&line)?;
+        }
+        out.flush()
+    }
+
+    // Portable Float Map, little endian and stored bottom row first
+    pub fn write_pfm_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "PF\n{} {}\n-1.0\n", self.w, self.h)?;
+
+        let mut line = Vec::with_capacity(self.w * 12);
+        for y in (0..self.h).rev() {
+            line.clear();
+            for x in 0..self.w {
+                let c = self.pixel_at(x, y);
+                for v in &[c.0, c.1, c.2] {
+                    line.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+            out.write_all(&line)?;
+        }
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rgbe() {
+        assert!(rgbe(Color::new(1.0, 1.0, 1.0)) == [128, 128, 128, 129]);
+        assert!(rgbe(Color::new(0.0, 0.0, 0.0)) == [0, 0, 0, 0]);
+        assert!(rgbe(Color::new(4.0, 2.0, 0.0)) == [128, 64, 0, 131]);
+        assert!(rgbe(Color::new(0.5, -1.0, 0.25)) == [128, 0, 64, 128]);
+        assert!(rgbe(Color::new(f32::MAX, 0.0, 1.0)) == [255, 0, 0, 255]);
+        assert!(rgbe(Color::new(f32::INFINITY, MAX_RGBE, MAX_RGBE / 2.0)) == [255, 255, 127, 255]);
+        assert!(rgbe(Color::new(f32::NAN, 1.0, 1.0)) == [0, 128, 128, 129]);
+    }
+
+    #[test]
+    fn test_rle() {
+        let mut out = Vec::new();
+        rle(&[1, 2, 3, 3, 3, 3, 3, 4], &mut out);
+        assert!(out == [2, 1, 2, 133, 3, 1, 4]);
+
+        let mut out = Vec::new();
+        rle(&[7; 300], &mut out);
+        assert!(out == [255, 7, 255, 7, 128 + 46, 7]);
+    }
+
+    #[test]
+    fn test_pfm() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.write_pixel(0, 0, Color::new(2.5, 0.0, -1.0));
+        let mut out = Vec::new();
+        canvas.write_pfm_to(&mut out).unwrap();
+        let header = b"PF\n2 2\n-1.0\n";
+        assert!(out.starts_with(header));
+        assert!(out.len() == header.len() + 2 * 2 * 12);
+
+        // the top left pixel comes after the whole bottom row
+        let first = &out[header.len() + 2 * 12..];
+        assert!(first[..4] == 2.5f32.to_le_bytes());
+        assert!(first[8..12] == (-1.0f32).to_le_bytes());
+    }
+}
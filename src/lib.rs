@@ -1,9 +1,38 @@
+// the precision of the renderer, enable the f64 feature for doubles
+#[cfg(not(feature = "f64"))]
+pub type Float = f32;
+#[cfg(feature = "f64")]
+pub type Float = f64;
+
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts;
+#[cfg(feature = "f64")]
+pub use std::f64::consts;
+
+pub mod error;
 pub mod ray;
 pub mod color;
 pub mod matrix;
+pub mod matrix4;
+pub mod transform;
+pub mod bounds;
 pub mod object;
 pub mod tuple;
+pub mod geometry;
+pub mod simd;
 pub mod light;
 pub mod material;
 pub mod world;
 pub mod camera;
+pub mod quaternion;
+pub mod trs;
+pub mod motion;
+pub mod aov;
+pub mod denoise;
+pub mod render;
+pub mod png;
+pub mod ppm;
+pub mod hdr;
+pub mod tonemap;
+pub mod yaml;
+pub mod scene;
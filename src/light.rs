@@ -1,8 +1,7 @@
 use crate::color::Color;
 use crate::tuple::Tuple;
-use crate::tuple::dot;
-use crate::material::Material;
-use crate::material::lightning;
+use crate::geometry::Point3;
+use crate::Float;
 
 pub struct PointLight<T> {
     pub(crate) intensity: Color<T>,
@@ -14,16 +13,37 @@ impl<T> PointLight<T> {
         Self { intensity, pos }
     }
 }
+
+impl<T: Copy> PointLight<T> {
+    pub fn intensity(&self) -> Color<T> {
+        self.intensity
+    }
+
+    pub fn pos(&self) -> Tuple<T> {
+        self.pos
+    }
+}
+
+impl PointLight<Float> {
+    // pos as a point for the renderer
+    pub fn position(&self) -> Point3 {
+        Point3::new(self.pos.0, self.pos.1, self.pos.2)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::geometry::{Vector3, Normal3};
+    use crate::material::Material;
+    use crate::material::lightning;
 
     #[test]
     fn test_lightning() {
-        let eyev = Tuple::new_vector(0.0, 0.0, -1.0);
-        let normalv = Tuple::new_vector(0.0, 0.0, -1.0);
+        let eyev = Vector3::new(0.0, 0.0, -1.0);
+        let normalv = Normal3::new(Vector3::new(0.0, 0.0, -1.0));
         let light = PointLight::new(Color::new(1.0, 1.0, 1.0), Tuple::new_point(0.0, 0.0, -10.0));
-        let pos = Tuple::new_point(0.0, 0.0, 0.0);
+        let pos = Point3::origin();
         let res = lightning(&Material::new(), &light, &pos, &eyev, &normalv, false);
         assert!(res == Color::new(1.9, 1.9, 1.9));
     }
@@ -1,15 +1,19 @@
 use crate::color::Color;
 use crate::light::PointLight;
-use crate::tuple::Tuple;
-use crate::tuple::dot;
+use crate::geometry::{Point3, Vector3, Normal3};
+use crate::Float;
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct Material {
-    pub color: Color<f32>,
-    pub ambient: f32,
-    pub diffuse: f32,
-    pub specular: f32,
-    pub shininess: f32,
+    pub color: Color<Float>,
+    pub ambient: Float,
+    pub diffuse: Float,
+    pub specular: Float,
+    pub shininess: Float,
+    // objects that don't cast shadows let all the light through
+    pub casts_shadow: bool,
+    // how much light gets through, tinted by the color, 0 is opaque
+    pub transparency: Float,
 }
 
 impl Material {
@@ -20,27 +24,45 @@ impl Material {
             diffuse: 0.9,
             specular: 0.9,
             shininess: 200.0,
+            casts_shadow: true,
+            transparency: 0.0,
         }
     }
+
+    // the part of the light behind the object that gets through it
+    pub fn shadow_filter(&self) -> Color<Float> {
+        if self.casts_shadow {
+            self.color * self.transparency
+        } else {
+            Color::new(1.0, 1.0, 1.0)
+        }
+    }
+}
+
+pub fn lightning(material: &Material, light: &PointLight<Float>, pos: &Point3, eyev: &Vector3, normalv: &Normal3, in_shadow: bool) -> Color<Float> {
+    let filter = if in_shadow { Color::new(0.0, 0.0, 0.0) } else { Color::new(1.0, 1.0, 1.0) };
+    lightning_filtered(material, light, pos, eyev, normalv, filter)
 }
 
-pub fn lightning(material: &Material, light: &PointLight<f32>, pos: &Tuple<f32>, eyev: &Tuple<f32>, normalv: &Tuple<f32>, in_shadow: bool) -> Color<f32> {
+// filter is the part of the light that reaches pos, see World::light_filter_at,
+// the ambient term doesn't depend on it
+pub fn lightning_filtered(material: &Material, light: &PointLight<Float>, pos: &Point3, eyev: &Vector3, normalv: &Normal3, filter: Color<Float>) -> Color<Float> {
     let black = Color::new(0.0, 0.0, 0.0);
     let effective_color = material.color * light.intensity;
-    let lightv = (light.pos - *pos).normalize();
+    let lightv = (light.position() - *pos).normalize();
 
     let ambient = effective_color * material.ambient;
 
-    if in_shadow { return ambient }
+    if filter == black { return ambient }
 
-    let light_dot_normal = dot(lightv, *normalv);
+    let light_dot_normal = normalv.dot(lightv);
     let (diffuse, specular) = if light_dot_normal < 0.0 {
         (black, black)
     } else {
         let diffuse = effective_color * material.diffuse * light_dot_normal;
 
-        let reflectv = (-lightv).reflect(normalv);
-        let reflect_dot_eye = dot(reflectv, *eyev);
+        let reflectv = (-lightv).reflect(*normalv);
+        let reflect_dot_eye = reflectv.dot(*eyev);
         let specular = if reflect_dot_eye < 0.0 {
             black
         } else {
@@ -51,21 +73,40 @@ pub fn lightning(material: &Material, light: &PointLight<f32>, pos: &Tuple<f32>,
         (diffuse, specular)
     };
 
-    ambient + diffuse + specular
+    ambient + (diffuse + specular) * filter
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::tuple::Tuple;
 
     #[test]
     fn test_lightning() {
-        let eyev = Tuple::new_vector(0.0, 0.0, -1.0);
-        let normalv = Tuple::new_vector(0.0, 0.0, -1.0);
+        let eyev = Vector3::new(0.0, 0.0, -1.0);
+        let normalv = Normal3::new(Vector3::new(0.0, 0.0, -1.0));
         let light = PointLight::new(Color::new(1.0, 1.0, 1.0), Tuple::new_point(0.0, 0.0, -10.0));
         let in_shadow = true;
         
-        let result = lightning(&Material::new(), &light, &Tuple::new_point(0.0, 0.0, 0.0), &eyev, &normalv, in_shadow);
+        let result = lightning(&Material::new(), &light, &Point3::origin(), &eyev, &normalv, in_shadow);
         assert!(result == Color::new(0.1, 0.1, 0.1));
     }
+
+    #[test]
+    fn test_shadow_filter() {
+        let mut m = Material::new();
+        assert!(m.shadow_filter() == Color::new(0.0, 0.0, 0.0));
+        m.color = Color::new(1.0, 0.5, 0.0);
+        m.transparency = 0.8;
+        assert!(m.shadow_filter() == Color::new(0.8, 0.4, 0.0));
+        m.casts_shadow = false;
+        assert!(m.shadow_filter() == Color::new(1.0, 1.0, 1.0));
+
+        let eyev = Vector3::new(0.0, 0.0, -1.0);
+        let normalv = Normal3::new(Vector3::new(0.0, 0.0, -1.0));
+        let light = PointLight::new(Color::new(1.0, 1.0, 1.0), Tuple::new_point(0.0, 0.0, -10.0));
+        let lit = |filter| lightning_filtered(&Material::new(), &light, &Point3::origin(), &eyev, &normalv, filter);
+        // ambient 0.1, diffuse 0.9 and specular 0.9
+        assert!(lit(Color::new(0.5, 0.0, 1.0)) == Color::new(1.0, 0.1, 1.9));
+    }
 }
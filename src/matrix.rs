@@ -1,6 +1,9 @@
 use std::ops;
 use crate::tuple::Tuple;
 use crate::tuple::cross;
+use crate::matrix4::Matrix4;
+use crate::quaternion::Quaternion;
+use crate::Float;
 use num_traits::Num;
 use num_traits::{Zero, One};
 use num_traits::real::Real;
@@ -150,6 +153,24 @@ impl<T: Real + Zero + std::iter::Sum> Matrix<T> {
     }
 }
 
+impl<T: Real> Matrix<T> {
+    pub fn shearing(xy: T, xz: T, yx: T, yz: T, zx: T, zy: T) -> Self {
+        Matrix4::shearing(xy, xz, yx, yz, zx, zy).into()
+    }
+
+    pub fn rotation_axis(axis: Tuple<T>, r: T) -> Self {
+        Matrix4::rotation_axis(axis, r).into()
+    }
+
+    pub fn reflection(point: Tuple<T>, normal: Tuple<T>) -> Self {
+        Matrix4::reflection(point, normal).into()
+    }
+
+    pub fn look_at(from: Tuple<T>, to: Tuple<T>, up: Tuple<T>) -> Self {
+        Matrix4::look_at(from, to, up).into()
+    }
+}
+
 impl<T: Num + Copy + Zero + std::iter::Sum> Matrix<T> {
     pub fn det(&self) -> T {
         if self.width == 1 && self.height == 1 {
@@ -266,37 +287,63 @@ impl<T: quickcheck::Arbitrary> quickcheck::Arbitrary for Matrix<T> {
     }
 }
 
-pub struct TransformBuilder<T>(Matrix<T>);
-impl<T: Real + One + Zero + Num + std::iter::Sum> TransformBuilder<T> {
+pub struct TransformBuilder<T>(Matrix4<T>);
+impl<T: Real> TransformBuilder<T> {
     pub fn identity() -> Self {
-        TransformBuilder(Matrix::eye(4))
+        TransformBuilder(Matrix4::identity())
     }
 
     pub fn translate(&self, x: T, y: T, z: T) -> Self {
-        TransformBuilder(&Matrix::translate(x, y, z) * &self.0)
+        TransformBuilder(Matrix4::translate(x, y, z) * self.0)
     }
 
     pub fn scale(&self, x: T, y: T, z: T) -> Self {
-        TransformBuilder(&Matrix::scale(x, y, z) * &self.0)
+        TransformBuilder(Matrix4::scale(x, y, z) * self.0)
     }
 
     pub fn rotation_x(&self, r: T) -> Self {
-        TransformBuilder(&Matrix::rotation_x(r) * &self.0)
+        TransformBuilder(Matrix4::rotation_x(r) * self.0)
     }
 
     pub fn rotation_y(&self, r: T) -> Self {
-        TransformBuilder(&Matrix::rotation_y(r) * &self.0)
+        TransformBuilder(Matrix4::rotation_y(r) * self.0)
     }
 
     pub fn rotation_z(&self, r: T) -> Self {
-        TransformBuilder(&Matrix::rotation_z(r) * &self.0)
+        TransformBuilder(Matrix4::rotation_z(r) * self.0)
+    }
+
+    pub fn shear(&self, xy: T, xz: T, yx: T, yz: T, zx: T, zy: T) -> Self {
+        TransformBuilder(Matrix4::shearing(xy, xz, yx, yz, zx, zy) * self.0)
     }
 
-    pub fn build(self) -> Matrix<T> {
+    pub fn rotation_axis(&self, axis: Tuple<T>, r: T) -> Self {
+        TransformBuilder(Matrix4::rotation_axis(axis, r) * self.0)
+    }
+
+    pub fn reflect(&self, point: Tuple<T>, normal: Tuple<T>) -> Self {
+        TransformBuilder(Matrix4::reflection(point, normal) * self.0)
+    }
+
+    pub fn look_at(&self, from: Tuple<T>, to: Tuple<T>, up: Tuple<T>) -> Self {
+        TransformBuilder(Matrix4::look_at(from, to, up) * self.0)
+    }
+
+    pub fn transform(&self, m: &Matrix4<T>) -> Self {
+        TransformBuilder(m * &self.0)
+    }
+
+    pub fn build(self) -> Matrix4<T> {
         self.0
     }
 }
 
+impl TransformBuilder<Float> {
+    pub fn rotate(&self, q: &Quaternion) -> Self {
+        TransformBuilder(q.to_matrix() * self.0)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -0,0 +1,420 @@
+use std::ops;
+use crate::matrix::Matrix;
+use crate::tuple::Tuple;
+use crate::tuple::cross;
+use num_traits::Num;
+use num_traits::{Zero, One};
+use num_traits::real::Real;
+
+// a 4x4 transform on the stack, stored row by row,
+// the general Matrix allocates on every operation and is too slow for rendering
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Matrix4<T> {
+    elems: [T; 16],
+}
+
+impl<T> Matrix4<T> {
+    pub fn new(elems: [T; 16]) -> Self {
+        Self { elems }
+    }
+
+    // row by row
+    pub fn as_array(&self) -> &[T; 16] {
+        &self.elems
+    }
+}
+
+impl<T: Copy + Zero> Matrix4<T> {
+    pub fn new_fn<F>(f: F) -> Self
+    where F: Fn(usize, usize) -> T {
+        let mut elems = [T::zero(); 16];
+        for (k, e) in elems.iter_mut().enumerate() {
+            *e = f(k / 4, k % 4);
+        }
+        Self { elems }
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self::new_fn(|i, j| self[(j, i)])
+    }
+}
+
+impl<T: Copy + Zero + One> Matrix4<T> {
+    pub fn identity() -> Self {
+        Self::new_fn(|i, j| if i == j { T::one() } else { T::zero() })
+    }
+}
+
+impl<T: Real> Matrix4<T> {
+    pub fn eq_real(&self, other: Matrix4<T>) -> bool {
+        self.eq_within(other, T::epsilon())
+    }
+
+    // every element at most tolerance away, for results that went through rounding
+    pub fn eq_within(&self, other: Matrix4<T>, tolerance: T) -> bool {
+        self.elems.iter().zip(other.elems.iter())
+            .all(|(&a, &b)| {
+                (a - b).abs() <= tolerance
+            })
+    }
+
+    pub fn translate(x: T, y: T, z: T) -> Self {
+        let mut m = Self::identity();
+        m[(0, 3)] = x;
+        m[(1, 3)] = y;
+        m[(2, 3)] = z;
+        m
+    }
+
+    pub fn scale(x: T, y: T, z: T) -> Self {
+        let mut m = Self::identity();
+        m[(0, 0)] = x;
+        m[(1, 1)] = y;
+        m[(2, 2)] = z;
+        m
+    }
+
+    pub fn rotation_x(r: T) -> Self {
+        let mut m = Self::identity();
+        m[(1, 1)] = r.cos();
+        m[(1, 2)] = -r.sin();
+        m[(2, 1)] = r.sin();
+        m[(2, 2)] = r.cos();
+        m
+    }
+
+    pub fn rotation_y(r: T) -> Self {
+        let mut m = Self::identity();
+        m[(0, 0)] = r.cos();
+        m[(0, 2)] = r.sin();
+        m[(2, 0)] = -r.sin();
+        m[(2, 2)] = r.cos();
+        m
+    }
+
+    pub fn rotation_z(r: T) -> Self {
+        let mut m = Self::identity();
+        m[(0, 0)] = r.cos();
+        m[(0, 1)] = -r.sin();
+        m[(1, 0)] = r.sin();
+        m[(1, 1)] = r.cos();
+        m
+    }
+
+    pub fn view_transform(from: Tuple<T>, to: Tuple<T>, up: Tuple<T>) -> Self {
+        let up = up.normalize();
+        let forward = (to - from).normalize();
+        let left = cross(forward, up);
+        let real_up = cross(left, forward);
+
+        let (o, z) = (T::one(), T::zero());
+        Self::new([
+            left.0,     left.1,     left.2,     z,
+            real_up.0,  real_up.1,  real_up.2,  z,
+            -forward.0, -forward.1, -forward.2, z,
+            z,          z,          z,          o,
+        ]) * Self::translate(-from.0, -from.1, -from.2)
+    }
+
+    // each coefficient moves one coordinate in proportion to another,
+    // xy moves x in proportion to y
+    pub fn shearing(xy: T, xz: T, yx: T, yz: T, zx: T, zy: T) -> Self {
+        let (o, z) = (T::one(), T::zero());
+        Self::new([
+            o,  xy, xz, z,
+            yx, o,  yz, z,
+            zx, zy, o,  z,
+            z,  z,  z,  o,
+        ])
+    }
+
+    // counterclockwise around axis when it points at you, like rotation_x/y/z
+    pub fn rotation_axis(axis: Tuple<T>, r: T) -> Self {
+        let Tuple(x, y, z, _) = axis.normalize();
+        let (s, c) = r.sin_cos();
+        let t = T::one() - c;
+        let (o, zero) = (T::one(), T::zero());
+        Self::new([
+            t * x * x + c,     t * x * y - s * z, t * x * z + s * y, zero,
+            t * x * y + s * z, t * y * y + c,     t * y * z - s * x, zero,
+            t * x * z - s * y, t * y * z + s * x, t * z * z + c,     zero,
+            zero,              zero,              zero,              o,
+        ])
+    }
+
+    // mirrors across the plane through point with the given normal
+    pub fn reflection(point: Tuple<T>, normal: Tuple<T>) -> Self {
+        let n = normal.normalize();
+        let two = T::one() + T::one();
+        let d = two * (n.0 * point.0 + n.1 * point.1 + n.2 * point.2);
+        let mut m = Self::new_fn(|i, j| {
+            let id = if i == j { T::one() } else { T::zero() };
+            if i < 3 && j < 3 { id - two * n[i] * n[j] } else { id }
+        });
+        m[(0, 3)] = d * n.0;
+        m[(1, 3)] = d * n.1;
+        m[(2, 3)] = d * n.2;
+        m
+    }
+
+    // places an object at from with its -z axis pointing at to and +y towards up,
+    // without scaling it, for an up perpendicular to the view this is the inverse of view_transform
+    pub fn look_at(from: Tuple<T>, to: Tuple<T>, up: Tuple<T>) -> Self {
+        let up = up.normalize();
+        let forward = (to - from).normalize();
+        let left = cross(forward, up).normalize();
+        let real_up = cross(left, forward);
+
+        let (o, z) = (T::one(), T::zero());
+        Self::new([
+            left.0, real_up.0, -forward.0, from.0,
+            left.1, real_up.1, -forward.1, from.1,
+            left.2, real_up.2, -forward.2, from.2,
+            z,      z,         z,          o,
+        ])
+    }
+
+    // the 2x2 determinants of the top two and bottom two rows,
+    // both det and inverse are built from them
+    fn subfactors(&self) -> ([T; 6], [T; 6]) {
+        let a = &self.elems;
+        let s = [
+            a[0] * a[5] - a[4] * a[1],
+            a[0] * a[6] - a[4] * a[2],
+            a[0] * a[7] - a[4] * a[3],
+            a[1] * a[6] - a[5] * a[2],
+            a[1] * a[7] - a[5] * a[3],
+            a[2] * a[7] - a[6] * a[3],
+        ];
+        let c = [
+            a[8] * a[13] - a[12] * a[9],
+            a[8] * a[14] - a[12] * a[10],
+            a[8] * a[15] - a[12] * a[11],
+            a[9] * a[14] - a[13] * a[10],
+            a[9] * a[15] - a[13] * a[11],
+            a[10] * a[15] - a[14] * a[11],
+        ];
+        (s, c)
+    }
+
+    pub fn det(&self) -> T {
+        let (s, c) = self.subfactors();
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+
+    pub fn inverse(&self) -> Option<Self> {
+        let (s, c) = self.subfactors();
+        let d = s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0];
+        // the determinant is at most the product of the lengths of the rows, so
+        // it's compared to that instead of a fixed number, small scales still invert
+        let a = &self.elems;
+        let size = (0..4)
+            .map(|i| a[i * 4..i * 4 + 4].iter().fold(T::zero(), |sum, &x| sum + x * x).sqrt())
+            .fold(T::one(), |p, l| p * l);
+        // written so NaN and infinities are None too
+        if !(d.abs() > T::epsilon() * size && d.abs() <= T::max_value()) {
+            return None
+        }
+
+        let inv = T::one() / d;
+        Some(Self::new([
+            ( a[5] * c[5] - a[6] * c[4] + a[7] * c[3]) * inv,
+            (-a[1] * c[5] + a[2] * c[4] - a[3] * c[3]) * inv,
+            ( a[13] * s[5] - a[14] * s[4] + a[15] * s[3]) * inv,
+            (-a[9] * s[5] + a[10] * s[4] - a[11] * s[3]) * inv,
+
+            (-a[4] * c[5] + a[6] * c[2] - a[7] * c[1]) * inv,
+            ( a[0] * c[5] - a[2] * c[2] + a[3] * c[1]) * inv,
+            (-a[12] * s[5] + a[14] * s[2] - a[15] * s[1]) * inv,
+            ( a[8] * s[5] - a[10] * s[2] + a[11] * s[1]) * inv,
+
+            ( a[4] * c[4] - a[5] * c[2] + a[7] * c[0]) * inv,
+            (-a[0] * c[4] + a[1] * c[2] - a[3] * c[0]) * inv,
+            ( a[12] * s[4] - a[13] * s[2] + a[15] * s[0]) * inv,
+            (-a[8] * s[4] + a[9] * s[2] - a[11] * s[0]) * inv,
+
+            (-a[4] * c[3] + a[5] * c[1] - a[6] * c[0]) * inv,
+            ( a[0] * c[3] - a[1] * c[1] + a[2] * c[0]) * inv,
+            (-a[12] * s[3] + a[13] * s[1] - a[14] * s[0]) * inv,
+            ( a[8] * s[3] - a[9] * s[1] + a[10] * s[0]) * inv,
+        ]))
+    }
+}
+
+impl<T> ops::Index<(usize, usize)> for Matrix4<T> {
+    type Output = T;
+
+    fn index(&self, idx: (usize, usize)) -> &Self::Output {
+        assert!(idx.0 < 4 && idx.1 < 4);
+        &self.elems[idx.0 * 4 + idx.1]
+    }
+}
+
+impl<T> ops::IndexMut<(usize, usize)> for Matrix4<T> {
+    fn index_mut(&mut self, idx: (usize, usize)) -> &mut Self::Output {
+        assert!(idx.0 < 4 && idx.1 < 4);
+        &mut self.elems[idx.0 * 4 + idx.1]
+    }
+}
+
+impl<T: Num + Copy> ops::Mul for Matrix4<T> {
+    type Output = Matrix4<T>;
+
+    fn mul(self, other: Self) -> Self::Output {
+        let (a, b) = (&self.elems, &other.elems);
+        let mut elems = [T::zero(); 16];
+        for i in 0..4 {
+            for j in 0..4 {
+                elems[i * 4 + j] = a[i * 4] * b[j] + a[i * 4 + 1] * b[4 + j]
+                    + a[i * 4 + 2] * b[8 + j] + a[i * 4 + 3] * b[12 + j];
+            }
+        }
+        Matrix4 { elems }
+    }
+}
+
+impl<T: Num + Copy> ops::Mul for &Matrix4<T> {
+    type Output = Matrix4<T>;
+
+    fn mul(self, other: Self) -> Self::Output {
+        *self * *other
+    }
+}
+
+impl<T: Num + Copy> ops::Mul<Tuple<T>> for Matrix4<T> {
+    type Output = Tuple<T>;
+
+    #[inline]
+    fn mul(self, t: Tuple<T>) -> Self::Output {
+        let a = &self.elems;
+        Tuple(
+            a[0] * t.0 + a[1] * t.1 + a[2] * t.2 + a[3] * t.3,
+            a[4] * t.0 + a[5] * t.1 + a[6] * t.2 + a[7] * t.3,
+            a[8] * t.0 + a[9] * t.1 + a[10] * t.2 + a[11] * t.3,
+            a[12] * t.0 + a[13] * t.1 + a[14] * t.2 + a[15] * t.3,
+        )
+    }
+}
+
+impl<T: Num + Copy> ops::Mul<Tuple<T>> for &Matrix4<T> {
+    type Output = Tuple<T>;
+
+    #[inline]
+    fn mul(self, t: Tuple<T>) -> Self::Output {
+        *self * t
+    }
+}
+
+impl<T: Copy> From<&Matrix<T>> for Matrix4<T> {
+    fn from(m: &Matrix<T>) -> Self {
+        let mut elems = [m[(0, 0)]; 16];
+        for (k, e) in elems.iter_mut().enumerate() {
+            *e = m[(k / 4, k % 4)];
+        }
+        Self { elems }
+    }
+}
+
+impl<T: Copy> From<Matrix4<T>> for Matrix<T> {
+    fn from(m: Matrix4<T>) -> Self {
+        Matrix::new((0..4).map(|i| m.elems[i * 4..i * 4 + 4].to_vec()).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample() -> Matrix<f64> {
+        Matrix::new(
+            vec![
+                vec![-5.0,  2.0,  6.0, -8.0],
+                vec![ 1.0, -5.0,  1.0,  8.0],
+                vec![ 7.0,  7.0, -6.0, -7.0],
+                vec![ 1.0, -3.0,  7.0,  4.0],
+            ]
+        )
+    }
+
+    #[test]
+    fn test_conversion() {
+        let m = sample();
+        let m4 = Matrix4::from(&m);
+        assert!(m4[(0, 3)] == -8.0);
+        assert!(m4[(3, 0)] == 1.0);
+        assert!(Matrix::from(m4) == m);
+        assert!(Matrix::from(Matrix4::<f64>::identity()) == Matrix::eye(4));
+        assert!(Matrix::from(Matrix4::translate(1.0, 2.0, 3.0)) == Matrix::translate(1.0, 2.0, 3.0));
+        assert!(Matrix::from(Matrix4::rotation_y(0.3)) == Matrix::rotation_y(0.3));
+    }
+
+    #[test]
+    fn test_mul() {
+        let a = sample();
+        let b = Matrix::rotation_x(0.5);
+        let ab = Matrix4::from(&a) * Matrix4::from(&b);
+        assert!(ab.eq_within(Matrix4::from(&(&a * &b)), 1e-10));
+
+        let t = Tuple::new_point(1.0, -2.0, 3.0);
+        assert!(Matrix4::from(&a) * t == &a * t);
+    }
+
+    #[test]
+    fn test_inverse() {
+        let m = sample();
+        let m4 = Matrix4::from(&m);
+        assert!(m4.det() == m.det());
+        assert!(m4.det() == 532.0);
+
+        let inv = m4.inverse().unwrap();
+        assert!(inv.eq_within(Matrix4::from(&m.inverse().unwrap()), 1e-10));
+        assert!((inv * m4).eq_within(Matrix4::identity(), 1e-10));
+
+        let singular = Matrix4::<f64>::scale(1.0, 0.0, 1.0);
+        assert!(singular.inverse().is_none());
+
+        // a tiny determinant isn't singular when the matrix itself is small
+        let small = Matrix4::<f32>::scale(0.001, 0.001, 0.001) * Matrix4::translate(2.0, 0.0, -1.0);
+        let inv = small.inverse().unwrap();
+        assert!((small * inv).eq_within(Matrix4::identity(), 1e-5));
+        assert!(inv.inverse().unwrap().eq_within(small, 1e-8));
+        assert!(Matrix4::<f32>::scale(1e-3, 1e-3, 0.0).inverse().is_none());
+        assert!(Matrix4::<f32>::scale(f32::NAN, 1.0, 1.0).inverse().is_none());
+    }
+
+    #[test]
+    fn test_view() {
+        let from = Tuple::new_point(1.0, 3.0, 2.0);
+        let to = Tuple::new_point(4.0, -2.0, 8.0);
+        let up = Tuple::new_vector(1.0, 1.0, 0.0);
+        let m = Matrix4::view_transform(from, to, up);
+        assert!(m.eq_within(Matrix4::from(&Matrix::view_transform(from, to, up)), 1e-10));
+    }
+
+    #[test]
+    fn test_shapes() {
+        let p = Tuple::new_point(2.0, 3.0, 4.0);
+        assert!(Matrix4::shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0) * p == Tuple::new_point(5.0, 3.0, 4.0));
+        assert!(Matrix4::shearing(0.0, 0.0, 0.0, 0.0, 0.0, 1.0) * p == Tuple::new_point(2.0, 3.0, 7.0));
+
+        let x = Tuple::new_vector(1.0, 0.0, 0.0);
+        assert!(Matrix4::rotation_axis(x * 3.0, 0.7).eq_within(Matrix4::rotation_x(0.7), 1e-10));
+        let axis = Tuple::new_vector(1.0, 1.0, 1.0);
+        let third = Matrix4::rotation_axis(axis, std::f64::consts::PI * 2.0 / 3.0);
+        assert!((third * x).eq_real(&Tuple::new_vector(0.0, 1.0, 0.0)));
+
+        let m = Matrix4::reflection(Tuple::new_point(0.0, 1.0, 0.0), Tuple::new_vector(0.0, 2.0, 0.0));
+        assert!(m * p == Tuple::new_point(2.0, -1.0, 4.0));
+        assert!(m * Tuple::new_vector(1.0, 1.0, 1.0) == Tuple::new_vector(1.0, -1.0, 1.0));
+        assert!(m.det() == -1.0);
+
+        let from = Tuple::new_point(1.0, 3.0, 2.0);
+        let to = Tuple::new_point(4.0, -2.0, 8.0);
+        let up = Tuple::new_vector(5.0, 3.0, 0.0);
+        let l = Matrix4::look_at(from, to, up);
+        assert!(l * Tuple::new_point(0.0, 0.0, 0.0) == from);
+        assert!(l.eq_within(Matrix4::view_transform(from, to, up).inverse().unwrap(), 1e-10));
+        let l = Matrix4::look_at(from, to, Tuple::new_vector(0.0, 1.0, 0.0));
+        assert!((l.det() - 1.0).abs() < 1e-10);
+    }
+}
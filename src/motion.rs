@@ -0,0 +1,82 @@
+use crate::matrix4::Matrix4;
+use crate::transform::Transform;
+use crate::trs::Trs;
+use crate::Float;
+
+// an object moving from one transform at time 0 to another at time 1
+#[derive(Clone, Debug, PartialEq)]
+pub struct Motion {
+    start: Trs,
+    end: Trs,
+    end_transform: Matrix4<Float>,
+}
+
+impl Motion {
+    // None if a transform in between can't be inverted, when one end is singular
+    // or when one end is mirrored and the other not, so a scale goes through 0,
+    // and when an end isn't exactly translate * rotate * scale, see Trs::decompose
+    pub fn new(start: &Matrix4<Float>, end: &Matrix4<Float>) -> Option<Self> {
+        let (s, e) = (Trs::decompose(start)?, Trs::decompose(end)?);
+        if (0..3).any(|i| s.scale[i] * e.scale[i] <= 0.0) {
+            return None
+        }
+        Some(Self { start: s, end: e, end_transform: *end })
+    }
+
+    pub fn end_transform(&self) -> &Matrix4<Float> {
+        &self.end_transform
+    }
+
+    pub fn at(&self, time: Float) -> Matrix4<Float> {
+        self.start.lerp(&self.end, time.clamp(0.0, 1.0)).to_matrix()
+    }
+
+    // at(time) with its inverse, cheap enough to do for every ray
+    pub fn transform_at(&self, time: Float) -> Transform {
+        self.start.lerp(&self.end, time.clamp(0.0, 1.0)).to_transform()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::TransformBuilder;
+
+    #[test]
+    fn test_motion() {
+        let start = TransformBuilder::identity()
+            .scale(1.0, 2.0, 1.0)
+            .translate(0.0, 0.0, 0.0)
+            .build();
+        let end = TransformBuilder::identity()
+            .scale(3.0, 2.0, 1.0)
+            .rotation_y(crate::consts::PI / 2.0)
+            .translate(4.0, 0.0, -2.0)
+            .build();
+        let m = Motion::new(&start, &end).unwrap();
+        assert!(m.at(0.0).eq_within(start, 1e-5));
+        assert!(m.at(1.0).eq_within(end, 1e-5));
+        assert!(m.at(2.0).eq_within(end, 1e-5));
+
+        let half = TransformBuilder::identity()
+            .scale(2.0, 2.0, 1.0)
+            .rotation_y(crate::consts::PI / 4.0)
+            .translate(2.0, 0.0, -1.0)
+            .build();
+        assert!(m.at(0.5).eq_within(half, 1e-5));
+        let t = m.transform_at(0.5);
+        assert!(t.matrix().eq_within(half, 1e-5));
+        assert!(t.inverse_matrix().eq_within(half.inverse().unwrap(), 1e-5));
+        assert!(m.transform_at(-1.0).inverse_matrix().eq_within(start.inverse().unwrap(), 1e-5));
+
+        // mirroring on the way would scale through 0
+        assert!(Motion::new(&start, &Matrix4::scale(-1.0, 1.0, 1.0)).is_none());
+        assert!(Motion::new(&start, &Matrix4::scale(1.0, 0.0, 1.0)).is_none());
+
+        // interpolating these as translate, rotate and scale would change them even at time 0
+        let sheared = Matrix4::shearing(0.5, 0.0, 0.0, 0.0, 0.0, 0.0);
+        assert!(Motion::new(&sheared, &end).is_none());
+        let stretched = Matrix4::scale(2.0, 1.0, 1.0) * Matrix4::rotation_z(crate::consts::PI / 4.0);
+        assert!(Motion::new(&start, &stretched).is_none());
+    }
+}
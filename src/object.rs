@@ -1,9 +1,16 @@
 use crate::ray::Ray;
-use crate::tuple::Tuple;
-use crate::tuple::dot;
-use crate::matrix::Matrix;
+use crate::ray::RayPacket;
+use crate::simd::Lanes;
+use crate::geometry::{Point3, Normal3};
+use crate::matrix4::Matrix4;
 use crate::matrix::TransformBuilder;
 use crate::material::Material;
+use crate::motion::Motion;
+use crate::trs::Trs;
+use crate::transform::Transform;
+use crate::error::Error;
+use std::borrow::Cow;
+use crate::Float;
 
 #[derive(PartialEq, Debug)]
 pub enum Shape {
@@ -13,53 +20,178 @@ pub enum Shape {
 #[derive(PartialEq, Debug)]
 pub struct Object {
     shape: Shape,
-    inv_transform: Matrix<f32>,
+    transform: Transform,
+    motion: Option<Motion>,
+    name: Option<String>,
     pub material: Material
 }
 
 #[derive(PartialEq, Debug)]
 pub struct Hitrecord<'a> {
-    pub hit: f32,
+    pub hit: Float,
     pub obj: &'a Object,
 }
 
 impl<'a> Hitrecord<'a> {
-    pub fn new(hit: f32, obj: &'a Object) -> Self {
+    pub fn new(hit: Float, obj: &'a Object) -> Self {
         Hitrecord { hit, obj }
     }
 
-    pub fn new_vec(hits: Vec<f32>, obj: &'a Object) -> Vec<Self> {
+    pub fn new_vec(hits: Vec<Float>, obj: &'a Object) -> Vec<Self> {
         hits.iter().map(|&h| Self::new(h, obj)).collect()
     }
 }
 
 impl Object {
     pub fn new(shape: Shape) -> Self {
-        Self { shape, inv_transform: Matrix::eye(4), material: Material::new() }
+        Self { shape, transform: Transform::identity(), motion: None, name: None, material: Material::new() }
     }
 
-    pub fn apply_transform(&mut self, transform: Matrix<f32>) {
-        // TODO: do error handling
-        self.inv_transform = transform.inverse().unwrap();
+    pub fn apply_transform(&mut self, transform: Matrix4<Float>) -> Result<(), Error> {
+        self.transform = Transform::new(transform).ok_or_else(|| Error::SingularTransform(self.describe()))?;
+        self.motion = None;
+        Ok(())
+    }
+
+    // moves from start at time 0 to end at time 1, see Camera::shutter
+    pub fn apply_motion(&mut self, start: Matrix4<Float>, end: Matrix4<Float>) -> Result<(), Error> {
+        let motion = Motion::new(&start, &end).ok_or_else(|| {
+            // both ends can be inverted, so one of them isn't translate * rotate * scale
+            if start.inverse().is_some() && end.inverse().is_some() && [&start, &end].iter().any(|m| Trs::decompose(m).is_none()) {
+                Error::UnsupportedMotion(self.describe())
+            } else {
+                Error::SingularMotion(self.describe())
+            }
+        })?;
+        self.transform = Transform::new(start).unwrap();
+        self.motion = Some(motion);
+        Ok(())
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = Some(name.to_string());
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    // how errors refer to the object
+    fn describe(&self) -> String {
+        match (&self.name, &self.shape) {
+            (Some(name), _) => format!("'{}'", name),
+            (None, Shape::Sphere(_)) => "an unnamed sphere".to_string(),
+        }
+    }
+
+    pub fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    // the transform at time 0
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn motion(&self) -> Option<&Motion> {
+        self.motion.as_ref()
+    }
+
+    fn transform_at(&self, time: Float) -> Cow<'_, Transform> {
+        match &self.motion {
+            None => Cow::Borrowed(&self.transform),
+            Some(m) => Cow::Owned(m.transform_at(time)),
+        }
     }
 
     pub fn hit<'a>(&'a self, r: &Ray) -> Vec<Hitrecord<'a>> {
-        let new_r = r.transform(&self.inv_transform);
-        Hitrecord::new_vec(self.shape.hit(&new_r), &self)
+        let mut hits = Vec::new();
+        self.hit_into(r, &mut hits);
+        hits
+    }
+
+    // adds the hits to out, which can be reused between rays
+    pub fn hit_into<'a>(&'a self, r: &Ray, out: &mut Vec<Hitrecord<'a>>) {
+        let new_r = self.transform_at(r.time).inv_ray(r);
+        self.shape.hit_each(&new_r, &mut |t| out.push(Hitrecord::new(t, self)));
+    }
+
+    // the nearest hit with 0 <= t < t_max, the local ray isn't normalized so t is the same
+    pub fn closest_hit(&self, r: &Ray, t_max: Float) -> Option<Float> {
+        let new_r = self.transform_at(r.time).inv_ray(r);
+        self.shape.closest_hit(&new_r, 0.0, t_max)
+    }
+
+    // the near and far hit of every ray in the packet, NaN where a ray misses
+    pub fn hit_packet<L: Lanes>(&self, r: &RayPacket<L>) -> [L; 2] {
+        match &self.motion {
+            None => self.shape.hit_packet(&r.transform(self.transform.inverse_matrix())),
+            // without a shutter interval every ray is at the same time
+            Some(m) if r.time.iter().all(|&t| t == r.time[0]) => {
+                self.shape.hit_packet(&r.transform(m.transform_at(r.time[0]).inverse_matrix()))
+            },
+            // the rays can be at different times, so each needs its own transform
+            Some(_) => {
+                let local = [0, 1, 2, 3].map(|i| {
+                    let ray = r.ray(i);
+                    self.transform_at(ray.time).inv_ray(&ray)
+                });
+                self.shape.hit_packet(&RayPacket::<L>::new(&local))
+            },
+        }
+    }
+
+    pub fn normal_at(&self, pt: Point3) -> Normal3 {
+        self.normal_at_time(pt, 0.0)
     }
 
-    pub fn normal_at(&self, pt: Tuple<f32>) -> Tuple<f32> {
-        let obj_pt = &self.inv_transform * pt;
-        let mut normal = &self.inv_transform.transpose() * self.shape.normal_at(obj_pt);
-        normal.3 = 0.0; // set w to 0
-        normal.normalize()
+    pub fn normal_at_time(&self, pt: Point3, time: Float) -> Normal3 {
+        let transform = self.transform_at(time);
+        let obj_pt = transform.inv_point(pt);
+        transform.normal(self.shape.normal_at(obj_pt))
     }
 }
 
 pub trait Hittable {
-    fn hit<'a>(&self, r: &Ray) -> Vec<f32>;
+    // calls f with every hit, nearest first, without allocating
+    fn hit_each(&self, r: &Ray, f: &mut dyn FnMut(Float));
 
-    fn normal_at(&self, pt: Tuple<f32>) -> Tuple<f32>;
+    fn hit(&self, r: &Ray) -> Vec<Float> {
+        let mut hits = Vec::new();
+        self.hit_each(r, &mut |t| hits.push(t));
+        hits
+    }
+
+    // the nearest hit with t_min <= t < t_max
+    fn closest_hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<Float> {
+        let mut closest = None;
+        self.hit_each(r, &mut |t| {
+            if t >= t_min && t < closest.unwrap_or(t_max) {
+                closest = Some(t);
+            }
+        });
+        closest
+    }
+
+    // the near and far hit of every ray, NaN where a ray misses,
+    // shapes that can test several rays at once should override this
+    fn hit_packet<L: Lanes>(&self, r: &RayPacket<L>) -> [L; 2] {
+        let mut near = [Float::NAN; 4];
+        let mut far = [Float::NAN; 4];
+        for i in 0..4 {
+            let mut first = true;
+            self.hit_each(&r.ray(i), &mut |t| {
+                if first {
+                    near[i] = t;
+                    first = false;
+                }
+                far[i] = t;
+            });
+        }
+        [L::from_array(&near), L::from_array(&far)]
+    }
+
+    fn normal_at(&self, pt: Point3) -> Normal3;
 }
 
 #[derive(PartialEq, Debug)]
@@ -72,39 +204,84 @@ impl Sphere {
 }
 
 impl Hittable for Shape {
-    fn hit<'a>(&self, r: &Ray) -> Vec<f32> {
+    fn hit_each(&self, r: &Ray, f: &mut dyn FnMut(Float)) {
+        match self {
+            Shape::Sphere(sphere) => sphere.hit_each(r, f),
+        }
+    }
+
+    fn closest_hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<Float> {
+        match self {
+            Shape::Sphere(sphere) => sphere.closest_hit(r, t_min, t_max),
+        }
+    }
+
+    fn hit_packet<L: Lanes>(&self, r: &RayPacket<L>) -> [L; 2] {
         match self {
-            Shape::Sphere(sphere) => sphere.hit(&r),
+            Shape::Sphere(sphere) => sphere.hit_packet(r),
         }
     }
 
-    fn normal_at(&self, pt: Tuple<f32>) -> Tuple<f32> {
+    fn normal_at(&self, pt: Point3) -> Normal3 {
         match self {
             Shape::Sphere(sphere) => sphere.normal_at(pt),
         }
     }
 }
 
-impl Hittable for Sphere {
-    fn hit<'a>(&self, r: &Ray) -> Vec<f32> {
-        let sphere_to_ray = r.origin - Tuple::new_point(0.0, 0.0, 0.0);
+impl Sphere {
+    // where the ray enters and leaves the sphere, None if it misses
+    fn roots(r: &Ray) -> Option<(Float, Float)> {
+        let sphere_to_ray = r.origin - Point3::origin();
 
-        let a = dot(r.dir, r.dir);
-        let b = 2.0 * dot(r.dir, sphere_to_ray);
-        let c = dot(sphere_to_ray, sphere_to_ray) - 1.0;
+        let a = r.dir.dot(r.dir);
+        let b = 2.0 * r.dir.dot(sphere_to_ray);
+        let c = sphere_to_ray.dot(sphere_to_ray) - 1.0;
 
         let disc = b * b - 4.0 * a * c;
-        if disc < 0.0 { 
-            vec![]
+        if disc < 0.0 {
+            None
         } else {
             let t1 = (-b - disc.sqrt()) / (2.0 * a);
             let t2 = (-b + disc.sqrt()) / (2.0 * a);
-            vec![t1, t2]
+            Some((t1, t2))
         }
     }
+}
 
-    fn normal_at(&self, pt: Tuple<f32>) -> Tuple<f32> {
-        (pt - Tuple::new_point(0.0, 0.0, 0.0)).normalize()
+impl Hittable for Sphere {
+    fn hit_each(&self, r: &Ray, f: &mut dyn FnMut(Float)) {
+        if let Some((t1, t2)) = Sphere::roots(r) {
+            f(t1);
+            f(t2);
+        }
+    }
+
+    fn closest_hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<Float> {
+        let (t1, t2) = Sphere::roots(r)?;
+        if t1 >= t_min && t1 < t_max {
+            Some(t1)
+        } else if t2 >= t_min && t2 < t_max {
+            Some(t2)
+        } else {
+            None
+        }
+    }
+
+    // the same as hit for four rays at once, a negative discriminant gives NaN
+    fn hit_packet<L: Lanes>(&self, r: &RayPacket<L>) -> [L; 2] {
+        let ([ox, oy, oz], [dx, dy, dz]) = (r.origin, r.dir);
+        let a = dx * dx + dy * dy + dz * dz;
+        let b = L::splat(2.0) * (dx * ox + dy * oy + dz * oz);
+        let c = ox * ox + oy * oy + oz * oz - L::splat(1.0);
+
+        let disc = (b * b - L::splat(4.0) * a * c).sqrt();
+        let (minus_b, two_a) = (L::splat(0.0) - b, L::splat(2.0) * a);
+        [(minus_b - disc) / two_a, (minus_b + disc) / two_a]
+    }
+
+    fn normal_at(&self, pt: Point3) -> Normal3 {
+        Normal3::new(pt - Point3::origin())
     }
 }
 
@@ -118,11 +295,12 @@ pub fn find_hit<'a>(hits: Vec<Hitrecord<'a>>) -> Option<Hitrecord<'a>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::geometry::Vector3;
 
     #[test]
     fn test_sphere() {
         let s = Object::new(Sphere::new());
-        let r = Ray::new(Tuple::new_point(0.0, 0.0, -5.0), Tuple::new_vector(0.0, 0.0, 1.0));
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
         let ht = s.hit(&r);
         assert!(ht.len() == 2);
         assert!(ht[0].hit == 4.0);
@@ -145,12 +323,12 @@ mod tests {
 
     #[test]
     fn test_intersect() {
-        let r = Ray::new(Tuple::new_point(0.0, 0.0, -5.0), Tuple::new_vector(0.0, 0.0, 1.0));
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
         let mut s = Object::new(Sphere::new());
-        s.apply_transform(Matrix::scale(2.0, 2.0, 2.0));
+        s.apply_transform(Matrix4::scale(2.0, 2.0, 2.0)).unwrap();
         let xs = s.hit(&r);
         assert!(xs.len() == 2);
-        println!("{:?}", xs.iter().map(|x| x.hit).collect::<Vec<f32>>());
+        println!("{:?}", xs.iter().map(|x| x.hit).collect::<Vec<Float>>());
         assert!(xs[0].hit == 3.0);
         assert!(xs[1].hit == 7.0);
     }
@@ -158,19 +336,100 @@ mod tests {
     #[test]
     fn test_normal() {
         let s = Sphere::new();
-        assert!(s.normal_at(Tuple::new_point(1.0, 0.0, 0.0)) == Tuple::new_vector(1.0, 0.0, 0.0));
+        assert!(s.normal_at(Point3::new(1.0, 0.0, 0.0)).to_vector() == Vector3::new(1.0, 0.0, 0.0));
 
-        let f = 3_f32.sqrt()/3.0;
-        assert!(s.normal_at(Tuple::new_point(f, f, f)).eq_real(&Tuple::new_vector(f, f, f)));
+        let f = (3.0 as Float).sqrt()/3.0;
+        assert!(s.normal_at(Point3::new(f, f, f)).to_vector().eq_real(&Vector3::new(f, f, f)));
 
         let mut sphere_obj = Object::new(s);
         sphere_obj.apply_transform(
             TransformBuilder::identity()
                 .translate(0.0, 1.0, 0.0)
                 .build()
-        );
-        let n = sphere_obj.normal_at(Tuple::new_point(0.0, 1.70711, -0.70711));
+        ).unwrap();
+        let n = sphere_obj.normal_at(Point3::new(0.0, 1.70711, -0.70711));
         println!("{:?}", n);
-        assert!(n.eq_real(&Tuple::new_vector(0.0, 0.7071068, -0.70710677,)));// my results are off by a bit
+        let h = (2.0 as Float).sqrt() / 2.0;
+        assert!(n.to_vector().eq_real(&Vector3::new(0.0, h, -h)));
+    }
+
+    #[test]
+    fn test_motion() {
+        let mut s = Object::new(Sphere::new());
+        s.apply_motion(Matrix4::identity(), Matrix4::translate(4.0, 0.0, 0.0)).unwrap();
+
+        let r = Ray::new_at(Point3::new(2.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0), 0.0);
+        assert!(s.hit(&r).len() == 0);
+
+        let r = Ray::new_at(Point3::new(2.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0), 0.5);
+        let xs = s.hit(&r);
+        assert!(xs.len() == 2);
+        assert!(xs[0].hit == 4.0);
+
+        let n = s.normal_at_time(Point3::new(4.0, 1.0, 0.0), 1.0);
+        assert!(n.to_vector().eq_real(&Vector3::new(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_closest_hit() {
+        let mut s = Object::new(Sphere::new());
+        s.apply_transform(Matrix4::scale(2.0, 2.0, 2.0)).unwrap();
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        assert!(s.closest_hit(&r, Float::INFINITY) == Some(3.0));
+        assert!(s.closest_hit(&r, 3.0).is_none());
+
+        // from inside only the far side is in front
+        let r = Ray::new(Point3::origin(), Vector3::new(0.0, 0.0, 1.0));
+        assert!(s.closest_hit(&r, Float::INFINITY) == Some(2.0));
+
+        let mut buf = Vec::new();
+        s.hit_into(&r, &mut buf);
+        s.hit_into(&r, &mut buf);
+        assert!(buf.iter().map(|h| h.hit).collect::<Vec<Float>>() == vec![-2.0, 2.0, -2.0, 2.0]);
+    }
+
+    #[test]
+    fn test_packet() {
+        let rays = [
+            Ray::new_at(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0), 0.0),
+            Ray::new_at(Point3::new(2.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0), 0.5),
+            Ray::new_at(Point3::new(0.0, 3.0, -5.0), Vector3::new(0.0, 0.0, 1.0), 1.0),
+            Ray::new_at(Point3::new(0.5, 0.0, 0.0), Vector3::new(1.0, 1.0, 0.0), 0.25),
+        ];
+        let packet: RayPacket = RayPacket::new(&rays);
+
+        let mut still = Object::new(Sphere::new());
+        still.apply_transform(Matrix4::translate(0.0, 1.0, 0.0) * Matrix4::scale(2.0, 2.0, 2.0)).unwrap();
+        let mut moving = Object::new(Sphere::new());
+        moving.apply_motion(Matrix4::identity(), Matrix4::translate(4.0, 0.0, 0.0)).unwrap();
+
+        for obj in [still, moving].iter() {
+            let [near, far] = obj.hit_packet(&packet);
+            let (near, far) = (near.to_array(), far.to_array());
+            for (i, r) in rays.iter().enumerate() {
+                match obj.hit(r).as_slice() {
+                    [t1, t2] => assert!((near[i] - t1.hit).abs() < 1e-4 && (far[i] - t2.hit).abs() < 1e-4),
+                    _ => assert!(near[i].is_nan() && far[i].is_nan()),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_singular() {
+        let mut s = Object::new(Sphere::new());
+        let e = s.apply_transform(Matrix4::scale(1.0, 0.0, 1.0)).err().unwrap();
+        assert!(e == Error::SingularTransform("an unnamed sphere".to_string()));
+        assert!(*s.transform() == Transform::identity());
+
+        s.set_name("ball");
+        let e = s.apply_motion(Matrix4::identity(), Matrix4::scale(-1.0, 1.0, 1.0)).err().unwrap();
+        assert!(e.to_string().contains("'ball'"));
+        assert!(s.motion().is_none());
+
+        let sheared = Matrix4::shearing(0.5, 0.0, 0.0, 0.0, 0.0, 0.0);
+        let e = s.apply_motion(sheared, Matrix4::identity()).err().unwrap();
+        assert!(e == Error::UnsupportedMotion("'ball'".to_string()));
+        assert!(s.motion().is_none() && *s.transform() == Transform::identity());
     }
 }
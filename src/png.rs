@@ -0,0 +1,458 @@
+use crate::color::{Canvas, quantize};
+use crate::tonemap::{DisplayTransform, ToneMap};
+
+const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    // no compression at all, fast and big
+    Stored,
+    // lz77 with the fixed huffman codes
+    Deflate,
+}
+
+#[derive(Clone, Debug)]
+pub struct PngOptions {
+    pub bit_depth: BitDepth,
+    // adds an sRGB chunk so viewers don't guess the color space
+    pub srgb: bool,
+    pub compression: Compression,
+    pub display: DisplayTransform,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PngOptions {
+    pub fn new() -> Self {
+        Self {
+            bit_depth: BitDepth::Eight,
+            srgb: true,
+            compression: Compression::Deflate,
+            display: DisplayTransform::srgb(ToneMap::Clamp),
+        }
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 == 1 { 0xedb88320 ^ (c >> 1) } else { c >> 1 };
+        }
+        *entry = c;
+    }
+
+    !data.iter().fold(0xffffffff, |c, &b| table[((c ^ b as u32) & 0xff) as usize] ^ (c >> 8))
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the most bytes we can add before b might overflow
+    for chunk in data.chunks(5552) {
+        for &x in chunk {
+            a += x as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+// bits are packed starting at the least significant bit, as deflate wants them
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u32,
+    n: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self { bytes: Vec::new(), acc: 0, n: 0 }
+    }
+
+    fn bits(&mut self, value: u32, count: u32) {
+        self.acc |= value << self.n;
+        self.n += count;
+        while self.n >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.n -= 8;
+        }
+    }
+
+    // huffman codes are stored most significant bit first
+    fn code(&mut self, code: u32, len: u32) {
+        let reversed = (0..len).fold(0, |r, i| (r << 1) | ((code >> i) & 1));
+        self.bits(reversed, len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.n > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
+
+const LENGTH_BASE: [u32; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u32; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+const WINDOW: usize = 32768;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 32;
+
+fn fixed_literal(w: &mut BitWriter, sym: u32) {
+    match sym {
+        0..=143 => w.code(0x30 + sym, 8),
+        144..=255 => w.code(0x190 + sym - 144, 9),
+        256..=279 => w.code(sym - 256, 7),
+        _ => w.code(0xc0 + sym - 280, 8),
+    }
+}
+
+fn fixed_match(w: &mut BitWriter, len: usize, dist: usize) {
+    let (len, dist) = (len as u32, dist as u32);
+    let l = LENGTH_BASE.iter().rposition(|&b| b <= len).unwrap();
+    fixed_literal(w, 257 + l as u32);
+    w.bits(len - LENGTH_BASE[l], LENGTH_EXTRA[l]);
+
+    let d = DIST_BASE.iter().rposition(|&b| b <= dist).unwrap();
+    w.code(d as u32, 5);
+    w.bits(dist - DIST_BASE[d], DIST_EXTRA[d]);
+}
+
+// a single block with the fixed huffman codes, matches are found with hash chains
+fn deflate_fixed(data: &[u8]) -> Vec<u8> {
+    let mut w = BitWriter::new();
+    w.bits(1, 1); // last block
+    w.bits(1, 2); // fixed huffman
+
+    let hash = |i: usize| {
+        ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize) & 0x7fff
+    };
+    let mut head = vec![usize::MAX; 0x8000];
+    let mut prev = vec![usize::MAX; data.len()];
+    let insert = |i: usize, head: &mut Vec<usize>, prev: &mut Vec<usize>| {
+        if i + 2 < data.len() {
+            let h = hash(i);
+            prev[i] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);
+        if i + 2 < data.len() {
+            let mut candidate = head[hash(i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW && chain < MAX_CHAIN {
+                let max = MAX_MATCH.min(data.len() - i);
+                let len = (0..max).take_while(|&k| data[candidate + k] == data[i + k]).count();
+                if len > best.0 {
+                    best = (len, i - candidate);
+                }
+                candidate = prev[candidate];
+                chain += 1;
+            }
+        }
+
+        if best.0 >= 3 {
+            fixed_match(&mut w, best.0, best.1);
+            for k in i..i + best.0 {
+                insert(k, &mut head, &mut prev);
+            }
+            i += best.0;
+        } else {
+            fixed_literal(&mut w, data[i] as u32);
+            insert(i, &mut head, &mut prev);
+            i += 1;
+        }
+    }
+
+    fixed_literal(&mut w, 256); // end of block
+    w.finish()
+}
+
+fn deflate_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 65535 * 5 + 5);
+    let mut blocks = data.chunks(65535).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(if blocks.peek().is_none() { 1 } else { 0 });
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out
+}
+
+fn zlib(data: &[u8], compression: Compression) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    match compression {
+        Compression::Stored => out.extend(deflate_stored(data)),
+        Compression::Deflate => out.extend(deflate_fixed(data)),
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc { a } else if pb <= pc { b } else { c }
+}
+
+// tries every filter type on a scanline and keeps the one that looks most compressible
+fn filter_row(row: &[u8], prev: &[u8], bpp: usize, out: &mut Vec<u8>) {
+    let filtered = |kind: u8| -> Vec<u8> {
+        (0..row.len()).map(|i| {
+            let a = if i >= bpp { row[i - bpp] } else { 0 };
+            let b = prev[i];
+            let c = if i >= bpp { prev[i - bpp] } else { 0 };
+            match kind {
+                0 => row[i],
+                1 => row[i].wrapping_sub(a),
+                2 => row[i].wrapping_sub(b),
+                3 => row[i].wrapping_sub(((a as u16 + b as u16) / 2) as u8),
+                _ => row[i].wrapping_sub(paeth(a, b, c)),
+            }
+        }).collect()
+    };
+    let cost = |v: &Vec<u8>| v.iter().map(|&x| (x as i8).unsigned_abs() as u32).sum::<u32>();
+
+    let (kind, best) = (0..5)
+        .map(|kind| (kind, filtered(kind)))
+        .min_by_key(|(_, v)| cost(v))
+        .unwrap();
+    out.push(kind);
+    out.extend(best);
+}
+
+impl Canvas {
+    pub fn write_png(&self, options: &PngOptions) -> Vec<u8> {
+        let (depth, max) = match options.bit_depth {
+            BitDepth::Eight => (8u8, 255),
+            BitDepth::Sixteen => (16u8, 65535),
+        };
+        let bpp = 3 * depth as usize / 8;
+
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&(self.w as u32).to_be_bytes());
+        ihdr.extend_from_slice(&(self.h as u32).to_be_bytes());
+        ihdr.extend_from_slice(&[depth, 2, 0, 0, 0]); // truecolor, no interlacing
+
+        let mut raw = Vec::with_capacity((self.w * bpp + 1) * self.h);
+        let mut prev = vec![0u8; self.w * bpp];
+        for y in 0..self.h {
+            let mut row = Vec::with_capacity(self.w * bpp);
+            for x in 0..self.w {
+                let c = quantize(options.display.apply(self.pixel_at(x, y)), max);
+                for &v in &[c.0, c.1, c.2] {
+                    match options.bit_depth {
+                        BitDepth::Eight => row.push(v as u8),
+                        BitDepth::Sixteen => row.extend_from_slice(&(v as u16).to_be_bytes()),
+                    }
+                }
+            }
+            match options.compression {
+                Compression::Stored => { raw.push(0); raw.extend_from_slice(&row); },
+                Compression::Deflate => filter_row(&row, &prev, bpp, &mut raw),
+            }
+            prev = row;
+        }
+
+        let mut out = SIGNATURE.to_vec();
+        write_chunk(&mut out, b"IHDR", &ihdr);
+        if options.srgb {
+            write_chunk(&mut out, b"sRGB", &[0]); // perceptual rendering intent
+        }
+        write_chunk(&mut out, b"IDAT", &zlib(&raw, options.compression));
+        write_chunk(&mut out, b"IEND", &[]);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    #[test]
+    fn test_checksums() {
+        assert!(crc32(b"IEND") == 0xae426082);
+        assert!(crc32(b"123456789") == 0xcbf43926);
+        assert!(adler32(b"Wikipedia") == 0x11e60398);
+    }
+
+    #[test]
+    fn test_stored_png() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.write_pixel(0, 0, Color::new(1.0, 0.5, 0.0));
+        canvas.write_pixel(1, 0, Color::new(-1.0, 2.0, 0.2));
+        let mut options = PngOptions::new();
+        options.compression = Compression::Stored;
+        options.srgb = false;
+        options.display = DisplayTransform::new();
+        let png = canvas.write_png(&options);
+
+        assert!(png[..8] == SIGNATURE);
+        assert!(png[8..16] == [0, 0, 0, 13, b'I', b'H', b'D', b'R']);
+        assert!(png[16..29] == [0, 0, 0, 2, 0, 0, 0, 1, 8, 2, 0, 0, 0]);
+
+        // IDAT: zlib header, one stored block, the filter byte and the pixels
+        let idat = &png[33..];
+        assert!(idat[..4] == [0, 0, 0, 18]);
+        assert!(idat[4..8] == *b"IDAT");
+        assert!(idat[8..10] == [0x78, 0x01]);
+        assert!(idat[10..15] == [1, 7, 0, 0xf8, 0xff]);
+        assert!(idat[15..22] == [0, 255, 128, 0, 0, 255, 51]);
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+    }
+
+    // a small inflate for stored and fixed huffman blocks, the two kinds we write
+    struct BitReader<'a> {
+        data: &'a [u8],
+        pos: usize,
+    }
+
+    impl<'a> BitReader<'a> {
+        fn bit(&mut self) -> u32 {
+            let b = (self.data[self.pos / 8] >> (self.pos % 8)) & 1;
+            self.pos += 1;
+            b as u32
+        }
+
+        fn bits(&mut self, count: u32) -> u32 {
+            (0..count).fold(0, |v, i| v | self.bit() << i)
+        }
+
+        // huffman codes come most significant bit first
+        fn fixed_symbol(&mut self) -> u32 {
+            let mut code = 0;
+            for len in 1..=9 {
+                code = code << 1 | self.bit();
+                match (len, code) {
+                    (7, 0..=23) => return 256 + code,
+                    (8, 0x30..=0xbf) => return code - 0x30,
+                    (8, 0xc0..=0xc7) => return 280 + code - 0xc0,
+                    (9, 0x190..=0x1ff) => return 144 + code - 0x190,
+                    _ => (),
+                }
+            }
+            panic!("bad fixed huffman code")
+        }
+    }
+
+    fn inflate(data: &[u8]) -> Vec<u8> {
+        let mut r = BitReader { data, pos: 0 };
+        let mut out = Vec::new();
+        loop {
+            let last = r.bit() == 1;
+            match r.bits(2) {
+                0 => {
+                    r.pos = r.pos.div_ceil(8) * 8;
+                    let len = r.bits(16) as usize;
+                    assert!(r.bits(16) as usize == !len & 0xffff);
+                    let start = r.pos / 8;
+                    out.extend_from_slice(&data[start..start + len]);
+                    r.pos += len * 8;
+                },
+                1 => loop {
+                    let sym = r.fixed_symbol();
+                    if sym < 256 {
+                        out.push(sym as u8);
+                        continue
+                    }
+                    if sym == 256 {
+                        break
+                    }
+                    let l = (sym - 257) as usize;
+                    let len = (LENGTH_BASE[l] + r.bits(LENGTH_EXTRA[l])) as usize;
+                    let d = (0..5).fold(0, |c, _| c << 1 | r.bit()) as usize;
+                    let dist = (DIST_BASE[d] + r.bits(DIST_EXTRA[d])) as usize;
+                    for _ in 0..len {
+                        out.push(out[out.len() - dist]);
+                    }
+                },
+                kind => panic!("block type {} isn't written by the encoder", kind),
+            }
+            if last {
+                return out
+            }
+        }
+    }
+
+    #[test]
+    fn test_inflate() {
+        // the same bytes zlib writes
+        assert!(deflate_fixed(b"abc") == [75, 76, 74, 6, 0]);
+        // zlib matches the 9 repeated bytes later, but inflates these to the same input
+        assert!(deflate_fixed(b"abcabcabcabc") == [75, 76, 74, 134, 35, 0]);
+
+        // more than one stored block, long runs and matches far back
+        let data = (0..70000)
+            .map(|i: usize| if i % 5000 < 300 { 9 } else { ((i * 7 % 251) ^ (i / 1000)) as u8 })
+            .collect::<Vec<u8>>();
+        assert!(inflate(&deflate_stored(&data)) == data);
+        assert!(inflate(&deflate_fixed(&data)) == data);
+        assert!(inflate(&deflate_stored(&[])).is_empty());
+        assert!(inflate(&deflate_fixed(&[])).is_empty());
+
+        let z = zlib(&data, Compression::Deflate);
+        let body = &z[2..z.len() - 4];
+        assert!(inflate(body) == data);
+        assert!(z[z.len() - 4..] == adler32(&data).to_be_bytes());
+    }
+
+    #[test]
+    fn test_deflate() {
+        // repetitive data has to come out much smaller than it went in
+        let data = (0..10000).map(|i| (i % 7) as u8).collect::<Vec<u8>>();
+        let compressed = deflate_fixed(&data);
+        assert!(compressed.len() < 500);
+
+        let canvas = Canvas::new_fn(64, 64, |x, _| Color::new(x as f32 / 64.0, 0.5, 0.25));
+        let mut options = PngOptions::new();
+        options.bit_depth = BitDepth::Sixteen;
+        assert!(canvas.write_png(&options).len() < 64 * 64 * 6 / 4);
+    }
+}
@@ -0,0 +1,173 @@
+use std::io;
+use std::io::Write;
+use rayon::prelude::*;
+use crate::color::{Canvas, Color, quantize};
+use crate::tonemap::DisplayTransform;
+
+const SCALING_FACTOR: u32 = 255;
+const PPM_LINE_LEN: usize = 70;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PpmFormat {
+    // P3, plain text
+    Ascii,
+    // P6, one byte per channel
+    Binary,
+}
+
+// writes P3 values, no line is longer than 70 characters,
+// or one pixel per line like the old write_ppm_fn_long
+struct AsciiWriter {
+    line_len: usize,
+    pixel_lines: bool,
+}
+
+impl AsciiWriter {
+    fn pixel(&mut self, buf: &mut Vec<u8>, c: Color<u32>) {
+        if self.pixel_lines {
+            buf.extend_from_slice(format!("{} {} {}\n", c.0, c.1, c.2).as_bytes());
+        } else {
+            self.push(buf, c.0);
+            self.push(buf, c.1);
+            self.push(buf, c.2);
+        }
+    }
+
+    fn push(&mut self, buf: &mut Vec<u8>, v: u32) {
+        let s = v.to_string();
+        if self.line_len == 0 {
+            self.line_len = s.len();
+        } else if self.line_len + s.len() + 1 > PPM_LINE_LEN {
+            buf.push(b'\n');
+            self.line_len = s.len();
+        } else {
+            buf.push(b' ');
+            self.line_len += s.len() + 1;
+        }
+        buf.extend_from_slice(s.as_bytes());
+    }
+}
+
+struct PpmWriter<'a, W: Write> {
+    out: &'a mut W,
+    format: PpmFormat,
+    display: &'a DisplayTransform,
+    ascii: AsciiWriter,
+    buf: Vec<u8>,
+}
+
+impl<'a, W: Write> PpmWriter<'a, W> {
+    fn new(out: &'a mut W, w: usize, h: usize, format: PpmFormat, display: &'a DisplayTransform) -> io::Result<Self> {
+        match format {
+            PpmFormat::Ascii => writeln!(out, "P3 {} {} {}", w, h, SCALING_FACTOR)?,
+            PpmFormat::Binary => writeln!(out, "P6 {} {} {}", w, h, SCALING_FACTOR)?,
+        }
+        let ascii = AsciiWriter { line_len: 0, pixel_lines: false };
+        Ok(Self { out, format, display, ascii, buf: Vec::with_capacity(w * 12) })
+    }
+
+    fn row(&mut self, row: &[Color<f32>]) -> io::Result<()> {
+        self.buf.clear();
+        for c in row {
+            let c = quantize(self.display.apply(*c), SCALING_FACTOR);
+            match self.format {
+                PpmFormat::Ascii => self.ascii.pixel(&mut self.buf, c),
+                PpmFormat::Binary => self.buf.extend_from_slice(&[c.0 as u8, c.1 as u8, c.2 as u8]),
+            }
+        }
+        self.out.write_all(&self.buf)
+    }
+
+    fn finish(self) -> io::Result<()> {
+        // we need a trailing newline, some programs (like imagemagick) need that in order to work
+        if self.format == PpmFormat::Ascii {
+            self.out.write_all(b"\n")?;
+        }
+        self.out.flush()
+    }
+}
+
+impl Canvas {
+    pub fn write_ppm_to<W: Write>(&self, out: &mut W, format: PpmFormat, display: &DisplayTransform) -> io::Result<()> {
+        let mut writer = PpmWriter::new(out, self.w, self.h, format, display)?;
+        let mut row = Vec::with_capacity(self.w);
+        for y in 0..self.h {
+            row.clear();
+            row.extend((0..self.w).map(|x| self.pixel_at(x, y)));
+            writer.row(&row)?;
+        }
+        writer.finish()
+    }
+
+    // renders and writes one row at a time, so only a row is ever kept in memory
+    pub fn write_ppm_fn_to<F, W>(w: usize, h: usize, f: F, out: &mut W, format: PpmFormat, display: &DisplayTransform) -> io::Result<()>
+    where
+        F: Fn(usize, usize) -> Color<f32> + std::marker::Sync,
+        W: Write {
+        let mut writer = PpmWriter::new(out, w, h, format, display)?;
+        for y in 0..h {
+            let row = (0..w).into_par_iter().map(|x| f(x, y)).collect::<Vec<Color<f32>>>();
+            writer.row(&row)?;
+        }
+        writer.finish()
+    }
+
+    // write_ppm_fn_to for P3 with every pixel on its own line
+    pub fn write_ppm_fn_long_to<F, W>(w: usize, h: usize, f: F, out: &mut W, display: &DisplayTransform) -> io::Result<()>
+    where
+        F: Fn(usize, usize) -> Color<f32> + std::marker::Sync,
+        W: Write {
+        let mut writer = PpmWriter::new(out, w, h, PpmFormat::Ascii, display)?;
+        writer.ascii.pixel_lines = true;
+        for y in 0..h {
+            let row = (0..w).into_par_iter().map(|x| f(x, y)).collect::<Vec<Color<f32>>>();
+            writer.row(&row)?;
+        }
+        writer.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tonemap::ToneMap;
+
+    #[test]
+    fn test_line_len() {
+        let canvas = Canvas::new_fn(100, 3, |x, y| Color::new(x as f32 / 99.0, 1.0, y as f32 / 2.0));
+        let mut out = Vec::new();
+        canvas.write_ppm_to(&mut out, PpmFormat::Ascii, &DisplayTransform::new()).unwrap();
+        let s = String::from_utf8(out).unwrap();
+        assert!(s.ends_with("\n"));
+        assert!(s.lines().all(|l| l.len() <= PPM_LINE_LEN));
+        assert!(s.lines().skip(1).flat_map(|l| l.split(' ')).count() == 100 * 3 * 3);
+    }
+
+    #[test]
+    fn test_long() {
+        let f = |x: usize, _| if x == 0 { Color::new(1.0, 0.5, 0.0) } else { Color::new(0.0, 0.0, 0.0) };
+        assert!(Canvas::write_ppm_fn_long(2, 1, f) == "P3 2 1 255\n255 128 0\n0 0 0\n\n");
+        assert!(Canvas::write_ppm_fn(2, 1, f) == "P3 2 1 255\n255 128 0 0 0 0\n");
+    }
+
+    #[test]
+    fn test_binary() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.write_pixel(1, 0, Color::new(1.5, 0.5, 0.0));
+        canvas.write_pixel(0, 1, Color::new(0.0, -0.5, 0.2));
+        let mut out = Vec::new();
+        canvas.write_ppm_to(&mut out, PpmFormat::Binary, &DisplayTransform::new()).unwrap();
+        let mut expected = b"P6 2 2 255\n".to_vec();
+        expected.extend_from_slice(&[0, 0, 0, 255, 128, 0, 0, 0, 51, 0, 0, 0]);
+        assert!(out == expected);
+
+        // rendering straight to the writer gives the same image
+        let mut streamed = Vec::new();
+        Canvas::write_ppm_fn_to(2, 2, |x, y| canvas.pixel_at(x, y), &mut streamed, PpmFormat::Binary, &DisplayTransform::new()).unwrap();
+        assert!(streamed == expected);
+
+        let mut encoded = Vec::new();
+        canvas.write_ppm_to(&mut encoded, PpmFormat::Binary, &DisplayTransform::srgb(ToneMap::Clamp)).unwrap();
+        assert!(encoded[11..23] == [0, 0, 0, 255, 188, 0, 0, 0, 124, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,200 @@
+use std::ops;
+use crate::matrix4::Matrix4;
+use crate::tuple::Tuple;
+use crate::Float;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quaternion {
+    pub w: Float,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+impl Quaternion {
+    pub fn new(w: Float, x: Float, y: Float, z: Float) -> Self {
+        Self { w, x, y, z }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    // a rotation by angle radians around axis, counterclockwise when the axis points at you
+    pub fn from_axis_angle(axis: Tuple<Float>, angle: Float) -> Self {
+        let axis = axis.normalize();
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        Self::new(cos, axis.0 * sin, axis.1 * sin, axis.2 * sin)
+    }
+
+    pub fn conjugate(&self) -> Quaternion {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    // rotates a vector or a point around the origin, q has to be normalized
+    pub fn rotate(&self, v: Tuple<Float>) -> Tuple<Float> {
+        let r = *self * Quaternion::new(0.0, v.0, v.1, v.2) * self.conjugate();
+        Tuple(r.x, r.y, r.z, v.3)
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> Float {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn normalize(&self) -> Quaternion {
+        *self * (1.0 / self.dot(self).sqrt())
+    }
+
+    // takes the upper 3x3 of m, which has to be a pure rotation
+    pub fn from_rotation_matrix(m: &Matrix4<Float>) -> Self {
+        let trace = m[(0, 0)] + m[(1, 1)] + m[(2, 2)];
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Self::new(
+                0.25 * s,
+                (m[(2, 1)] - m[(1, 2)]) / s,
+                (m[(0, 2)] - m[(2, 0)]) / s,
+                (m[(1, 0)] - m[(0, 1)]) / s,
+            )
+        } else if m[(0, 0)] > m[(1, 1)] && m[(0, 0)] > m[(2, 2)] {
+            let s = (1.0 + m[(0, 0)] - m[(1, 1)] - m[(2, 2)]).sqrt() * 2.0;
+            Self::new(
+                (m[(2, 1)] - m[(1, 2)]) / s,
+                0.25 * s,
+                (m[(0, 1)] + m[(1, 0)]) / s,
+                (m[(0, 2)] + m[(2, 0)]) / s,
+            )
+        } else if m[(1, 1)] > m[(2, 2)] {
+            let s = (1.0 + m[(1, 1)] - m[(0, 0)] - m[(2, 2)]).sqrt() * 2.0;
+            Self::new(
+                (m[(0, 2)] - m[(2, 0)]) / s,
+                (m[(0, 1)] + m[(1, 0)]) / s,
+                0.25 * s,
+                (m[(1, 2)] + m[(2, 1)]) / s,
+            )
+        } else {
+            let s = (1.0 + m[(2, 2)] - m[(0, 0)] - m[(1, 1)]).sqrt() * 2.0;
+            Self::new(
+                (m[(1, 0)] - m[(0, 1)]) / s,
+                (m[(0, 2)] + m[(2, 0)]) / s,
+                (m[(1, 2)] + m[(2, 1)]) / s,
+                0.25 * s,
+            )
+        };
+        q.normalize()
+    }
+
+    pub fn to_matrix(&self) -> Matrix4<Float> {
+        let Quaternion { w, x, y, z } = self.normalize();
+        Matrix4::new([
+            1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z),       2.0 * (x * z + w * y),       0.0,
+            2.0 * (x * y + w * z),       1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x),       0.0,
+            2.0 * (x * z - w * y),       2.0 * (y * z + w * x),       1.0 - 2.0 * (x * x + y * y), 0.0,
+            0.0,                         0.0,                         0.0,                         1.0,
+        ])
+    }
+
+    pub fn slerp(&self, other: &Quaternion, t: Float) -> Quaternion {
+        // q and -q are the same rotation, take the shorter way around
+        let mut cos = self.dot(other);
+        let other = if cos < 0.0 {
+            cos = -cos;
+            -*other
+        } else {
+            *other
+        };
+
+        if cos > 0.9995 {
+            // too close for the sine to be stable, lerp instead
+            return (*self * (1.0 - t) + other * t).normalize()
+        }
+
+        let theta = cos.acos();
+        let sin = theta.sin();
+        *self * (((1.0 - t) * theta).sin() / sin) + other * ((t * theta).sin() / sin)
+    }
+}
+
+impl ops::Add<Quaternion> for Quaternion {
+    type Output = Quaternion;
+
+    fn add(self, other: Quaternion) -> Quaternion {
+        Quaternion::new(self.w + other.w, self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+// the rotation other followed by self, like multiplying rotation matrices
+impl ops::Mul<Quaternion> for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, other: Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+            self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+        )
+    }
+}
+
+impl ops::Mul<Float> for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, other: Float) -> Quaternion {
+        Quaternion::new(self.w * other, self.x * other, self.y * other, self.z * other)
+    }
+}
+
+impl ops::Neg for Quaternion {
+    type Output = Quaternion;
+
+    fn neg(self) -> Quaternion {
+        Quaternion::new(-self.w, -self.x, -self.y, -self.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PI: Float = crate::consts::PI;
+
+
+    #[test]
+    fn test_matrix_roundtrip() {
+        let m = Matrix4::rotation_y(PI / 3.0);
+        let q = Quaternion::from_rotation_matrix(&m);
+        assert!(q.to_matrix().eq_within(m, 1e-5));
+
+        let m = Matrix4::rotation_x(PI * 0.9) * Matrix4::rotation_z(-PI / 4.0);
+        let q = Quaternion::from_rotation_matrix(&m);
+        assert!(q.to_matrix().eq_within(m, 1e-5));
+    }
+
+    #[test]
+    fn test_slerp() {
+        let a = Quaternion::identity();
+        let b = Quaternion::from_rotation_matrix(&Matrix4::rotation_z(PI / 2.0));
+        let half = a.slerp(&b, 0.5).to_matrix();
+        assert!(half.eq_within(Matrix4::rotation_z(PI / 4.0), 1e-5));
+
+        assert!(a.slerp(&b, 0.0) == a);
+        assert!(a.slerp(&b, 1.0).to_matrix().eq_within(b.to_matrix(), 1e-5));
+    }
+
+    #[test]
+    fn test_axis_angle() {
+        let q = Quaternion::from_axis_angle(Tuple::new_vector(0.0, 2.0, 0.0), PI / 3.0);
+        assert!(q.to_matrix().eq_within(Matrix4::rotation_y(PI / 3.0), 1e-5));
+
+        let p = Tuple::new_point(1.0, 0.0, 0.0);
+        let r = q.rotate(p);
+        assert!(r.eq_real(&(Matrix4::rotation_y(PI / 3.0) * p)));
+        assert!(r.3 == 1.0);
+
+        // composing like matrices, the right one first
+        let a = Quaternion::from_axis_angle(Tuple::new_vector(1.0, 0.0, 0.0), 0.4);
+        let b = Quaternion::from_axis_angle(Tuple::new_vector(0.0, 0.0, 1.0), -1.1);
+        assert!((a * b).to_matrix().eq_within(Matrix4::rotation_x(0.4) * Matrix4::rotation_z(-1.1), 1e-5));
+    }
+}
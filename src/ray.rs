@@ -1,25 +1,80 @@
-use crate::tuple::Tuple;
-use crate::matrix::Matrix;
+use crate::geometry::{Point3, Vector3};
+use crate::matrix4::Matrix4;
+use crate::simd::{F4, Lanes};
+use crate::Float;
 
 #[derive(Debug)]
 pub struct Ray {
-    pub origin: Tuple<f32>,
-    pub dir: Tuple<f32>,
+    pub origin: Point3,
+    pub dir: Vector3,
+    // when in the shutter interval the ray was shot, for motion blur
+    pub time: Float,
 }
 
 impl Ray {
-    pub fn new(origin: Tuple<f32>, dir: Tuple<f32>) -> Self {
-        Self { origin, dir: dir.normalize() }
+    pub fn new(origin: Point3, dir: Vector3) -> Self {
+        Self { origin, dir: dir.normalize(), time: 0.0 }
     }
 
-    pub fn pos(&self, t: f32) -> Tuple<f32> {
+    pub fn new_at(origin: Point3, dir: Vector3, time: Float) -> Self {
+        Self { origin, dir: dir.normalize(), time }
+    }
+
+    pub fn pos(&self, t: Float) -> Point3 {
         self.origin + self.dir * t
     }
 
-    pub fn transform(&self, m: &Matrix<f32>) -> Self {
+    pub fn transform(&self, m: &Matrix4<Float>) -> Self {
         // don't call Ray::new because it normalizes dir
         // Found the bug!
-        Self { origin: m * self.origin, dir: m * self.dir }
+        Self { origin: *m * self.origin, dir: *m * self.dir, time: self.time }
+    }
+}
+
+// four rays side by side, one per lane, so they can be intersected together,
+// the coordinates are split up so origin[0] holds the x of every ray
+#[derive(Clone, Copy, Debug)]
+pub struct RayPacket<L = F4> {
+    pub origin: [L; 3],
+    pub dir: [L; 3],
+    pub time: [Float; 4],
+}
+
+impl<L: Lanes> RayPacket<L> {
+    pub fn new(rays: &[Ray; 4]) -> Self {
+        let lanes = |f: &dyn Fn(&Ray) -> Float| L::new(f(&rays[0]), f(&rays[1]), f(&rays[2]), f(&rays[3]));
+        Self {
+            origin: [lanes(&|r| r.origin.x), lanes(&|r| r.origin.y), lanes(&|r| r.origin.z)],
+            dir: [lanes(&|r| r.dir.x), lanes(&|r| r.dir.y), lanes(&|r| r.dir.z)],
+            time: [rays[0].time, rays[1].time, rays[2].time, rays[3].time],
+        }
+    }
+
+    pub fn ray(&self, i: usize) -> Ray {
+        let (o, d) = (self.origin, self.dir);
+        let lane = |v: L| v.to_array()[i];
+        Ray {
+            origin: Point3::new(lane(o[0]), lane(o[1]), lane(o[2])),
+            dir: Vector3::new(lane(d[0]), lane(d[1]), lane(d[2])),
+            time: self.time[i],
+        }
+    }
+
+    // like Ray::transform, every ray goes through the same matrix
+    pub fn transform(&self, m: &Matrix4<Float>) -> Self {
+        let row = |i: usize, v: [L; 3]| {
+            L::splat(m[(i, 0)]) * v[0] + L::splat(m[(i, 1)]) * v[1] + L::splat(m[(i, 2)]) * v[2]
+        };
+        let (o, d) = (self.origin, self.dir);
+        Self {
+            origin: [
+                row(0, o) + L::splat(m[(0, 3)]),
+                row(1, o) + L::splat(m[(1, 3)]),
+                row(2, o) + L::splat(m[(2, 3)]),
+            ],
+            dir: [row(0, d), row(1, d), row(2, d)],
+            time: self.time,
+        }
     }
 }
 
@@ -29,17 +84,35 @@ mod tests {
 
     #[test]
     fn test_transform() {
-        let r = Ray::new(Tuple::new_point(1.0, 2.0, 3.0), Tuple::new_vector(0.0, 1.0, 0.0));
-        let m = Matrix::translate(3.0, 4.0, 5.0);
+        let r = Ray::new(Point3::new(1.0, 2.0, 3.0), Vector3::new(0.0, 1.0, 0.0));
+        let m = Matrix4::translate(3.0, 4.0, 5.0);
         let r2 = r.transform(&m);
 
-        assert!(r2.origin == Tuple::new_point(4.0, 6.0, 8.0));
-        assert!(r2.dir == Tuple::new_vector(0.0, 1.0, 0.0));
+        assert!(r2.origin == Point3::new(4.0, 6.0, 8.0));
+        assert!(r2.dir == Vector3::new(0.0, 1.0, 0.0));
 
-        let r = Ray::new(Tuple::new_point(0.0, 0.0, -5.0), Tuple::new_vector(0.0, 0.0, 1.0));
-        let m = Matrix::scale(2.0, 2.0, 2.0);
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        let m = Matrix4::scale(2.0, 2.0, 2.0);
         let r2 = r.transform(&m);
-        assert!(r2.origin == Tuple::new_point(0.0, 0.0, -10.0));
-        assert!(r2.dir == Tuple::new_vector(0.0, 0.0, 2.0));
+        assert!(r2.origin == Point3::new(0.0, 0.0, -10.0));
+        assert!(r2.dir == Vector3::new(0.0, 0.0, 2.0));
+    }
+
+    #[test]
+    fn test_packet() {
+        let rays = [
+            Ray::new_at(Point3::new(1.0, 2.0, 3.0), Vector3::new(0.0, 1.0, 0.0), 0.0),
+            Ray::new_at(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0), 0.25),
+            Ray::new_at(Point3::new(-1.0, 0.5, 0.0), Vector3::new(1.0, 1.0, 0.0), 0.5),
+            Ray::new_at(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0), 1.0),
+        ];
+        let p: RayPacket = RayPacket::new(&rays);
+        let m = Matrix4::translate(3.0, 4.0, 5.0) * Matrix4::scale(2.0, 2.0, 2.0);
+        let tp = p.transform(&m);
+        for (i, r) in rays.iter().enumerate() {
+            assert!(p.ray(i).origin == r.origin && p.ray(i).dir == r.dir && p.ray(i).time == r.time);
+            let (a, b) = (tp.ray(i), r.transform(&m));
+            assert!(a.origin.eq_real(&b.origin) && a.dir.eq_real(&b.dir));
+        }
     }
 }
@@ -0,0 +1,238 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use rayon::prelude::*;
+use crate::camera::Camera;
+use crate::color::Canvas;
+use crate::color::Color;
+use crate::world::World;
+use crate::aov::Aovs;
+
+pub struct RenderOptions {
+    pub tile_size: usize,
+    // called with (finished tiles, total tiles) from the worker threads
+    pub progress: Option<Box<dyn Fn(usize, usize) + Sync>>,
+    // checked before every tile, tiles that never started stay black,
+    // whoever set it knows the image isn't finished
+    pub cancel: Option<Arc<AtomicBool>>,
+    // trace 2x2 blocks of pixels as packets of four rays, otherwise
+    // every ray is traced on its own like Camera::render_pixel
+    pub packets: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenderOptions {
+    pub fn new() -> Self {
+        Self { tile_size: 32, progress: None, cancel: None, packets: true }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        match &self.cancel {
+            Some(flag) => flag.load(Ordering::Relaxed),
+            None => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Tile {
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+}
+
+fn tiles(w: usize, h: usize, size: usize) -> Vec<Tile> {
+    let size = size.max(1);
+    (0..h).step_by(size)
+        .flat_map(|y| (0..w).step_by(size).map(move |x| {
+            Tile { x, y, w: size.min(w - x), h: size.min(h - y) }
+        }))
+        .collect()
+}
+
+// runs f on every tile on the worker threads, None for the tiles that were cancelled
+fn render_tiles<T, F>(tiles: &[Tile], options: &RenderOptions, f: F) -> Vec<Option<Vec<T>>>
+where
+    T: Send,
+    F: Fn(&Tile) -> Vec<T> + Sync,
+{
+    let done = AtomicUsize::new(0);
+    tiles.par_iter()
+        .map(|tile| {
+            if options.is_cancelled() {
+                return None
+            }
+
+            let pixels = f(tile);
+
+            let finished = done.fetch_add(1, Ordering::Relaxed) + 1;
+            if let Some(progress) = &options.progress {
+                progress(finished, tiles.len());
+            }
+            Some(pixels)
+        })
+        .collect()
+}
+
+pub fn render(world: &World, camera: &Camera, options: &RenderOptions) -> Canvas {
+    let (w, h) = (camera.width(), camera.height());
+    let tiles = tiles(w, h, options.tile_size);
+
+    let rendered = render_tiles(&tiles, options, |tile| {
+        // 2x2 blocks of pixels are traced together, the last row or
+        // column of an odd sized tile a pixel at a time
+        let mut colors = vec![Color::new(0.0, 0.0, 0.0); tile.w * tile.h];
+        for by in (0..tile.h).step_by(2) {
+            for bx in (0..tile.w).step_by(2) {
+                if options.packets && bx + 1 < tile.w && by + 1 < tile.h {
+                    let block = camera.render_block(world, tile.x + bx, tile.y + by);
+                    for (k, c) in block.iter().enumerate() {
+                        colors[(by + k / 2) * tile.w + bx + k % 2] = c.to_f32();
+                    }
+                } else {
+                    for y in by..(by + 2).min(tile.h) {
+                        for x in bx..(bx + 2).min(tile.w) {
+                            colors[y * tile.w + x] = camera.render_pixel(world, tile.x + x, tile.y + y).to_f32();
+                        }
+                    }
+                }
+            }
+        }
+        colors
+    });
+
+    let mut canvas = Canvas::new(w, h);
+    for (tile, colors) in tiles.iter().zip(rendered) {
+        if let Some(colors) = colors {
+            for (i, c) in colors.into_iter().enumerate() {
+                canvas.write_pixel(tile.x + i % tile.w, tile.y + i / tile.w, c);
+            }
+        }
+    }
+    canvas
+}
+
+// render and the depth, normal, albedo and id passes, traced a pixel at a time
+// so every pass comes from the same rays as the color
+pub fn render_aovs(world: &World, camera: &Camera, options: &RenderOptions) -> (Canvas, Aovs) {
+    let (w, h) = (camera.width(), camera.height());
+    let tiles = tiles(w, h, options.tile_size);
+
+    let rendered = render_tiles(&tiles, options, |tile| {
+        (0..tile.w * tile.h)
+            .map(|i| camera.render_pixel_aovs(world, tile.x + i % tile.w, tile.y + i / tile.w))
+            .collect::<Vec<_>>()
+    });
+
+    let mut canvas = Canvas::new(w, h);
+    let mut aovs = Aovs::new(w, h);
+    for (tile, pixels) in tiles.iter().zip(rendered) {
+        if let Some(pixels) = pixels {
+            for (i, (c, aov)) in pixels.into_iter().enumerate() {
+                let (x, y) = (tile.x + i % tile.w, tile.y + i / tile.w);
+                canvas.write_pixel(x, y, c.to_f32());
+                aovs.write_sample(x, y, &aov);
+            }
+        }
+    }
+    (canvas, aovs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Point3, Vector3};
+
+    fn scene() -> (World, Camera) {
+        let w = World::new_default();
+        let mut c = Camera::new(21.0, 13.0, crate::consts::PI / 2.0);
+        c.set_view(
+            Point3::new(0.0, 0.0, -5.0),
+            Point3::origin(),
+            Vector3::new(0.0, 1.0, 0.0),
+        ).unwrap();
+        (w, c)
+    }
+
+    #[test]
+    fn test_tiles() {
+        let t = tiles(5, 3, 2);
+        assert!(t.len() == 6);
+        assert!(t[2] == Tile { x: 4, y: 0, w: 1, h: 2 });
+        assert!(t[5] == Tile { x: 4, y: 2, w: 1, h: 1 });
+    }
+
+    #[test]
+    fn test_render() {
+        let (w, c) = scene();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut options = RenderOptions::new();
+        options.tile_size = 4;
+        let counter = calls.clone();
+        options.progress = Some(Box::new(move |done, total| {
+            assert!(done <= total && total == 24);
+            counter.fetch_add(1, Ordering::Relaxed);
+        }));
+        let canvas = render(&w, &c, &options);
+        assert!(calls.load(Ordering::Relaxed) == 24);
+        assert!(canvas.w == 21 && canvas.h == 13);
+        for &(x, y) in &[(0, 0), (10, 6), (20, 12), (13, 5)] {
+            assert!(canvas.pixel_at(x, y) == c.render_pixel(&w, x, y).to_f32());
+        }
+
+        // odd tiles have a last row and column outside of any block
+        options.tile_size = 3;
+        options.progress = None;
+        let canvas = render(&w, &c, &options);
+        options.packets = false;
+        let single = render(&w, &c, &options);
+        for y in 0..13 {
+            for x in 0..21 {
+                assert!(canvas.pixel_at(x, y) == c.render_pixel(&w, x, y).to_f32());
+                assert!(single.pixel_at(x, y) == canvas.pixel_at(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_aovs() {
+        let (w, c) = scene();
+        let mut options = RenderOptions::new();
+        options.tile_size = 4;
+        let (canvas, aovs) = render_aovs(&w, &c, &options);
+        assert!(canvas.w == 21 && aovs.depth.h == 13);
+        for &(x, y) in &[(0, 0), (10, 6), (20, 12), (13, 5)] {
+            let (color, aov) = c.render_pixel_aovs(&w, x, y);
+            assert!(canvas.pixel_at(x, y) == color.to_f32());
+            assert!(aovs.id_at(x, y) == aov.id);
+            assert!(aovs.albedo.pixel_at(x, y) == aov.albedo.to_f32());
+        }
+        assert!(aovs.id_at(10, 6) == 1 && aovs.id_at(0, 0) == 0);
+        assert!(aovs.mask(1).pixel_at(10, 6) == Color::new(1.0, 1.0, 1.0));
+        assert!(aovs.mask(1).pixel_at(0, 0) == Color::new(0.0, 0.0, 0.0));
+
+        let visible = aovs.visible();
+        assert!(visible.normal.pixel_at(0, 0) == Color::new(0.5, 0.5, 0.5));
+        let d = visible.depth.pixel_at(10, 6).0;
+        assert!(d > 0.0 && d <= 1.0);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let (w, c) = scene();
+        let mut options = RenderOptions::new();
+        options.cancel = Some(Arc::new(AtomicBool::new(true)));
+        let canvas = render(&w, &c, &options);
+        assert!(canvas.pixel_at(10, 6) == Color::new(0.0, 0.0, 0.0));
+        assert!(render_aovs(&w, &c, &options).1.id_at(10, 6) == 0);
+
+        // a flag that is never set doesn't stop anything
+        options.cancel = Some(Arc::new(AtomicBool::new(false)));
+        assert!(render(&w, &c, &options).pixel_at(10, 6) == c.render_pixel(&w, 10, 6).to_f32());
+    }
+}
@@ -0,0 +1,803 @@
+// scene files in the style of the Ray Tracer Challenge yaml scenes
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use crate::camera::{Camera, Projection};
+use crate::color::Color;
+use crate::light::PointLight;
+use crate::material::Material;
+use crate::matrix::TransformBuilder;
+use crate::matrix4::Matrix4;
+use crate::transform::Transform;
+use crate::quaternion::Quaternion;
+use crate::trs::Trs;
+use crate::object::{Object, Shape, Sphere};
+use crate::tuple::{Tuple, cross};
+use crate::geometry::{Point3, Vector3};
+use crate::world::{World, Epsilon, DEFAULT_EPSILON};
+use crate::yaml;
+use crate::yaml::{Node, Value};
+use crate::Float;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // line 0 is for errors that aren't about a place in the file
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+impl From<yaml::ParseError> for SceneError {
+    fn from(e: yaml::ParseError) -> Self {
+        SceneError { line: e.line, message: e.message }
+    }
+}
+
+fn error<T>(node: &Node, message: String) -> Result<T, SceneError> {
+    Err(SceneError { line: node.line, message })
+}
+
+pub struct Scene {
+    pub world: World,
+    pub camera: Camera,
+}
+
+fn scalar(node: &Node) -> Result<&str, SceneError> {
+    match &node.value {
+        Value::Scalar(s) => Ok(s),
+        _ => error(node, "expected a single value".to_string()),
+    }
+}
+
+fn seq(node: &Node) -> Result<&Vec<Node>, SceneError> {
+    match &node.value {
+        Value::Seq(items) => Ok(items),
+        _ => error(node, "expected a list".to_string()),
+    }
+}
+
+fn map(node: &Node) -> Result<&Vec<(String, Node)>, SceneError> {
+    match &node.value {
+        Value::Map(entries) => Ok(entries),
+        _ => error(node, "expected a map".to_string()),
+    }
+}
+
+fn number(node: &Node) -> Result<Float, SceneError> {
+    let s = scalar(node)?;
+    match s.parse::<Float>() {
+        Ok(x) => Ok(x),
+        Err(_) => error(node, format!("expected a number, found '{}'", s)),
+    }
+}
+
+fn integer(node: &Node) -> Result<usize, SceneError> {
+    let s = scalar(node)?;
+    match s.parse::<usize>() {
+        Ok(x) => Ok(x),
+        Err(_) => error(node, format!("expected a positive integer, found '{}'", s)),
+    }
+}
+
+fn boolean(node: &Node) -> Result<bool, SceneError> {
+    match scalar(node)? {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        s => error(node, format!("expected true or false, found '{}'", s)),
+    }
+}
+
+fn numbers(node: &Node, n: usize) -> Result<Vec<Float>, SceneError> {
+    let items = seq(node)?;
+    if items.len() != n {
+        return error(node, format!("expected {} numbers, found {}", n, items.len()))
+    }
+    items.iter().map(number).collect()
+}
+
+fn point(node: &Node) -> Result<Point3, SceneError> {
+    let v = numbers(node, 3)?;
+    Ok(Point3::new(v[0], v[1], v[2]))
+}
+
+fn vector(node: &Node) -> Result<Vector3, SceneError> {
+    let v = numbers(node, 3)?;
+    Ok(Vector3::new(v[0], v[1], v[2]))
+}
+
+fn color(node: &Node) -> Result<Color<Float>, SceneError> {
+    let v = numbers(node, 3)?;
+    Ok(Color::new(v[0], v[1], v[2]))
+}
+
+struct Loader {
+    defines: HashMap<String, Node>,
+    camera: Option<Camera>,
+    world: World,
+}
+
+impl Loader {
+    fn define(&mut self, item: &Node) -> Result<(), SceneError> {
+        let mut name = None;
+        let mut value = None;
+        let mut extend = None;
+        for (key, v) in map(item)? {
+            match key.as_str() {
+                "define" => name = Some(scalar(v)?.to_string()),
+                "value" => value = Some(v),
+                "extend" => extend = Some(v),
+                _ => return error(v, format!("unknown key '{}' in define", key)),
+            }
+        }
+        let name = name.unwrap();
+        let value = match value {
+            Some(v) => v.clone(),
+            None => return error(item, format!("define '{}' has no value", name)),
+        };
+
+        let value = match extend {
+            None => value,
+            Some(base) => {
+                // the base is copied first, then overridden by the value
+                let base = self.lookup(base)?;
+                let mut entries = map(&base)?.clone();
+                for (k, v) in map(&value)? {
+                    match entries.iter_mut().find(|(bk, _)| bk == k) {
+                        Some(entry) => entry.1 = v.clone(),
+                        None => entries.push((k.clone(), v.clone())),
+                    }
+                }
+                Node::new(Value::Map(entries), value.line)
+            },
+        };
+        self.defines.insert(name, value);
+        Ok(())
+    }
+
+    fn lookup(&self, name: &Node) -> Result<Node, SceneError> {
+        let s = scalar(name)?;
+        match self.defines.get(s) {
+            Some(v) => Ok(v.clone()),
+            None => error(name, format!("'{}' is not defined", s)),
+        }
+    }
+
+    fn material(&self, node: &Node) -> Result<Material, SceneError> {
+        let node = match node.value {
+            Value::Scalar(_) => self.lookup(node)?,
+            _ => node.clone(),
+        };
+        let mut m = Material::new();
+        for (key, v) in map(&node)? {
+            match key.as_str() {
+                "color" => m.color = color(v)?,
+                "ambient" => m.ambient = number(v)?,
+                "diffuse" => m.diffuse = number(v)?,
+                "specular" => m.specular = number(v)?,
+                "shininess" => m.shininess = number(v)?,
+                "casts-shadow" => m.casts_shadow = boolean(v)?,
+                "transparency" => m.transparency = number(v)?,
+                _ => return error(v, format!("unknown material property '{}'", key)),
+            }
+        }
+        Ok(m)
+    }
+
+    // operations are applied in the order they are listed, visiting holds
+    // the defines being expanded so one that refers to itself is an error
+    fn transform_ops(&self, node: &Node, t: TransformBuilder<Float>, visiting: &mut Vec<String>) -> Result<TransformBuilder<Float>, SceneError> {
+        let mut t = t;
+        for op in seq(node)? {
+            t = match &op.value {
+                Value::Scalar(name) => {
+                    if visiting.contains(name) {
+                        return error(op, format!("'{}' refers to itself", name))
+                    }
+                    visiting.push(name.clone());
+                    let t = self.transform_ops(&self.lookup(op)?, t, visiting)?;
+                    visiting.pop();
+                    t
+                },
+                Value::Seq(args) if !args.is_empty() => {
+                    let name = scalar(&args[0])?;
+                    let v = args[1..].iter().map(number).collect::<Result<Vec<Float>, SceneError>>()?;
+                    let expect = |n: usize| {
+                        if v.len() == n { Ok(()) } else { error(op, format!("'{}' takes {} numbers", name, n)) }
+                    };
+                    let nonzero = |d: Tuple<Float>| {
+                        if d.magnitude() > 0.0 { Ok(d) } else { error(op, format!("'{}' doesn't give a direction", name)) }
+                    };
+                    match name {
+                        "translate" => { expect(3)?; t.translate(v[0], v[1], v[2]) },
+                        "scale" => { expect(3)?; t.scale(v[0], v[1], v[2]) },
+                        "rotate-x" => { expect(1)?; t.rotation_x(v[0]) },
+                        "rotate-y" => { expect(1)?; t.rotation_y(v[0]) },
+                        "rotate-z" => { expect(1)?; t.rotation_z(v[0]) },
+                        "quaternion" => { expect(4)?; t.rotate(&Quaternion::new(v[0], v[1], v[2], v[3])) },
+                        "shear" => { expect(6)?; t.shear(v[0], v[1], v[2], v[3], v[4], v[5]) },
+                        // axis x, y, z and the angle
+                        "rotate" => {
+                            expect(4)?;
+                            let axis = nonzero(Tuple::new_vector(v[0], v[1], v[2]))?;
+                            t.rotation_axis(axis, v[3])
+                        },
+                        // a point on the plane and its normal
+                        "reflect" => {
+                            expect(6)?;
+                            let normal = nonzero(Tuple::new_vector(v[3], v[4], v[5]))?;
+                            t.reflect(Tuple::new_point(v[0], v[1], v[2]), normal)
+                        },
+                        // from, to and up, like the camera
+                        "look-at" => {
+                            expect(9)?;
+                            let (from, to) = (Tuple::new_point(v[0], v[1], v[2]), Tuple::new_point(v[3], v[4], v[5]));
+                            let up = Tuple::new_vector(v[6], v[7], v[8]);
+                            nonzero(cross(to - from, up))?;
+                            t.look_at(from, to, up)
+                        },
+                        // all 16 entries, row by row
+                        "matrix" => {
+                            expect(16)?;
+                            t.transform(&Matrix4::new_fn(|i, j| v[i * 4 + j]))
+                        },
+                        _ => return error(op, format!("unknown transform '{}'", name)),
+                    }
+                },
+                _ => return error(op, "expected a transform like [ translate, 1, 2, 3 ]".to_string()),
+            };
+        }
+        Ok(t)
+    }
+
+    fn transform(&self, node: &Node) -> Result<Matrix4<Float>, SceneError> {
+        let lone_matrix = match seq(node)?.as_slice() {
+            [op] => match &op.value {
+                Value::Seq(args) => args.len() == 17 && scalar(&args[0])? == "matrix",
+                _ => false,
+            },
+            _ => false,
+        };
+        let m = if lone_matrix {
+            // taken as it is, multiplying with the identity would turn -0 into 0
+            let args = seq(&seq(node)?[0])?;
+            let v = args[1..].iter().map(number).collect::<Result<Vec<Float>, SceneError>>()?;
+            Matrix4::new_fn(|i, j| v[i * 4 + j])
+        } else {
+            self.transform_ops(node, TransformBuilder::identity(), &mut Vec::new())?.build()
+        };
+        Ok(m)
+    }
+
+    fn camera(&mut self, item: &Node) -> Result<(), SceneError> {
+        if self.camera.is_some() {
+            return error(item, "the scene already has a camera".to_string())
+        }
+
+        let required = |key: &str| match item.get(key) {
+            Some(v) => Ok(v),
+            None => error(item, format!("camera needs '{}'", key)),
+        };
+        let width = integer(required("width")?)?;
+        let height = integer(required("height")?)?;
+        let fov = number(required("field-of-view")?)?;
+        let mut c = Camera::new(width as Float, height as Float, fov);
+
+        let mut from = Point3::origin();
+        let mut to = Point3::new(0.0, 0.0, -1.0);
+        let mut up = Vector3::new(0.0, 1.0, 0.0);
+        // places the camera like an object, instead of from, to and up
+        let mut transform = None;
+        for (key, v) in map(item)? {
+            match key.as_str() {
+                "add" | "width" | "height" | "field-of-view" => (),
+                "from" => from = point(v)?,
+                "to" => to = point(v)?,
+                "up" => up = vector(v)?,
+                "transform" => match Transform::new(self.transform(v)?) {
+                    Some(t) => transform = Some(t),
+                    None => return error(v, "the camera transform can't be inverted".to_string()),
+                },
+                "aperture" => c.aperture_radius = number(v)?,
+                "focal-distance" => c.focal_distance = number(v)?,
+                "samples" => c.samples = integer(v)?,
+                "shutter" => {
+                    let s = numbers(v, 2)?;
+                    c.shutter = (s[0], s[1]);
+                },
+                "projection" => c.projection = match &v.value {
+                    Value::Scalar(s) if s == "perspective" => Projection::Perspective,
+                    Value::Scalar(s) if s == "fisheye" => Projection::Fisheye,
+                    Value::Scalar(s) if s == "equirectangular" => Projection::Equirectangular,
+                    Value::Seq(args) if args.len() == 2 && scalar(&args[0])? == "orthographic" => {
+                        Projection::Orthographic(number(&args[1])?)
+                    },
+                    _ => return error(v, "unknown projection".to_string()),
+                },
+                _ => return error(v, format!("unknown camera property '{}'", key)),
+            }
+        }
+
+        if transform.is_some() && ["from", "to", "up"].iter().any(|k| item.get(k).is_some()) {
+            return error(item, "camera has both a transform and from/to/up".to_string())
+        }
+        match transform {
+            Some(m) => c.set_transform(m),
+            None => if let Err(e) = c.set_view(from, to, up) {
+                return error(item, e.to_string())
+            },
+        }
+        self.camera = Some(c);
+        Ok(())
+    }
+
+    fn light(&mut self, item: &Node) -> Result<(), SceneError> {
+        let mut at = Point3::origin();
+        let mut intensity = Color::new(1.0, 1.0, 1.0);
+        for (key, v) in map(item)? {
+            match key.as_str() {
+                "add" => (),
+                "at" => at = point(v)?,
+                "intensity" => intensity = color(v)?,
+                _ => return error(v, format!("unknown light property '{}'", key)),
+            }
+        }
+        self.world.add_light(PointLight::new(intensity, at.into()));
+        Ok(())
+    }
+
+    fn object(&mut self, item: &Node, mut obj: Object) -> Result<(), SceneError> {
+        // first, so errors about the object can use the name
+        if let Some(v) = item.get("name") {
+            obj.set_name(scalar(v)?);
+        }
+        let mut end = None;
+        for (key, v) in map(item)? {
+            match key.as_str() {
+                "add" | "name" => (),
+                "material" => obj.material = self.material(v)?,
+                "transform" => if let Err(e) = obj.apply_transform(self.transform(v)?) {
+                    return error(v, e.to_string())
+                },
+                // the transform at the end of the shutter interval, for motion blur
+                "end-transform" => end = Some(self.transform(v)?),
+                _ => return error(v, format!("unknown property '{}'", key)),
+            }
+        }
+        if let Some(end) = end {
+            let start = match item.get("transform") {
+                Some(v) => self.transform(v)?,
+                None => Matrix4::identity(),
+            };
+            if let Err(e) = obj.apply_motion(start, end) {
+                return error(item.get("end-transform").unwrap(), e.to_string())
+            }
+        }
+        self.world.add_object(obj);
+        Ok(())
+    }
+
+    // settings of the world as a whole
+    fn set(&mut self, item: &Node) -> Result<(), SceneError> {
+        let setting = item.get("set").unwrap();
+        let value = match item.get("value") {
+            Some(v) => v,
+            None => return error(item, format!("set '{}' has no value", scalar(setting)?)),
+        };
+        for (key, v) in map(item)? {
+            if key != "set" && key != "value" {
+                return error(v, format!("unknown key '{}' in set", key))
+            }
+        }
+        match scalar(setting)? {
+            // how far rays starting on a surface are moved off it, a distance or auto
+            "epsilon" => self.world.set_epsilon(match scalar(value)? {
+                "auto" => Epsilon::Auto,
+                _ => match number(value)? {
+                    e if e.is_finite() && e > 0.0 => Epsilon::Fixed(e),
+                    e => return error(value, format!("epsilon must be a positive distance, found '{}'", e)),
+                },
+            }),
+            s => return error(setting, format!("unknown setting '{}'", s)),
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, item: &Node) -> Result<(), SceneError> {
+        let kind = item.get("add").unwrap();
+        match scalar(kind)? {
+            "camera" => self.camera(item),
+            "light" => self.light(item),
+            "sphere" => self.object(item, Object::new(Sphere::new())),
+            s => error(kind, format!("don't know how to add '{}'", s)),
+        }
+    }
+}
+
+pub fn load(src: &str) -> Result<Scene, SceneError> {
+    let doc = yaml::parse(src)?;
+    let mut loader = Loader { defines: HashMap::new(), camera: None, world: World::new() };
+
+    for item in seq(&doc)? {
+        if item.get("add").is_some() {
+            loader.add(item)?;
+        } else if item.get("define").is_some() {
+            loader.define(item)?;
+        } else if item.get("set").is_some() {
+            loader.set(item)?;
+        } else {
+            return error(item, "expected 'add', 'define' or 'set'".to_string())
+        }
+    }
+
+    match loader.camera {
+        Some(camera) => Ok(Scene { world: loader.world, camera }),
+        None => error(&doc, "the scene has no camera".to_string()),
+    }
+}
+
+pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+    match std::fs::read_to_string(&path) {
+        Ok(src) => load(&src),
+        Err(e) => Err(SceneError { line: 0, message: e.to_string() }),
+    }
+}
+
+// every Float is written so that it parses back to exactly the same value
+fn list(v: &[Float]) -> String {
+    format!("[ {} ]", v.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", "))
+}
+
+// a double quoted scalar, so names can hold any character
+fn quote(s: &str) -> String {
+    let escaped = s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+fn op(name: &str, v: &[Float]) -> String {
+    format!("[ {}, {} ]", name, v.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", "))
+}
+
+// scale, rotation and translation when they build exactly m again
+fn trs_ops(m: &Matrix4<Float>) -> Option<Vec<String>> {
+    let Trs { translation: tr, rotation: q, scale: sc } = Trs::decompose(m)?;
+    let mut ops = Vec::new();
+    let mut t = TransformBuilder::identity();
+    if (sc.0, sc.1, sc.2) != (1.0, 1.0, 1.0) {
+        ops.push(op("scale", &[sc.0, sc.1, sc.2]));
+        t = t.scale(sc.0, sc.1, sc.2);
+    }
+    if q != Quaternion::identity() {
+        ops.push(op("quaternion", &[q.w, q.x, q.y, q.z]));
+        t = t.rotate(&q);
+    }
+    if (tr.0, tr.1, tr.2) != (0.0, 0.0, 0.0) {
+        ops.push(op("translate", &[tr.0, tr.1, tr.2]));
+        t = t.translate(tr.0, tr.1, tr.2);
+    }
+    if !ops.is_empty() && t.build() == *m { Some(ops) } else { None }
+}
+
+fn transform_block(out: &mut String, key: &str, m: &Matrix4<Float>) {
+    let ops = trs_ops(m).unwrap_or_else(|| {
+        let entries = (0..4).flat_map(|i| (0..4).map(move |j| m[(i, j)])).collect::<Vec<Float>>();
+        vec![op("matrix", &entries)]
+    });
+    out.push_str(&format!("  {}:\n", key));
+    for o in ops {
+        out.push_str(&format!("    - {}\n", o));
+    }
+}
+
+fn material_block(out: &mut String, m: &Material) {
+    let default = Material::new();
+    let mut lines = Vec::new();
+    if m.color != default.color {
+        lines.push(format!("color: {}", list(&[m.color.0, m.color.1, m.color.2])));
+    }
+    for &(name, v, d) in &[
+        ("ambient", m.ambient, default.ambient),
+        ("diffuse", m.diffuse, default.diffuse),
+        ("specular", m.specular, default.specular),
+        ("shininess", m.shininess, default.shininess),
+        ("transparency", m.transparency, default.transparency),
+    ] {
+        if v != d {
+            lines.push(format!("{}: {}", name, v));
+        }
+    }
+    if m.casts_shadow != default.casts_shadow {
+        lines.push(format!("casts-shadow: {}", m.casts_shadow));
+    }
+    if !lines.is_empty() {
+        out.push_str("  material:\n");
+        for l in lines {
+            out.push_str(&format!("    {}\n", l));
+        }
+    }
+}
+
+// writes a scene file that load() turns back into the same world and camera
+pub fn save(world: &World, camera: &Camera) -> String {
+    let mut out = String::new();
+
+    out.push_str("- add: camera\n");
+    out.push_str(&format!("  width: {}\n  height: {}\n", camera.width(), camera.height()));
+    out.push_str(&format!("  field-of-view: {}\n", camera.fov()));
+    transform_block(&mut out, "transform", camera.transform().matrix());
+    match camera.projection {
+        Projection::Perspective => (),
+        Projection::Orthographic(w) => out.push_str(&format!("  projection: [ orthographic, {} ]\n", w)),
+        Projection::Fisheye => out.push_str("  projection: fisheye\n"),
+        Projection::Equirectangular => out.push_str("  projection: equirectangular\n"),
+    }
+    if camera.aperture_radius != 0.0 {
+        out.push_str(&format!("  aperture: {}\n", camera.aperture_radius));
+    }
+    if camera.focal_distance != 1.0 {
+        out.push_str(&format!("  focal-distance: {}\n", camera.focal_distance));
+    }
+    if camera.samples != 1 {
+        out.push_str(&format!("  samples: {}\n", camera.samples));
+    }
+    if camera.shutter != (0.0, 0.0) {
+        out.push_str(&format!("  shutter: {}\n", list(&[camera.shutter.0, camera.shutter.1])));
+    }
+
+    match world.epsilon() {
+        e if e == DEFAULT_EPSILON => (),
+        Epsilon::Fixed(e) => out.push_str(&format!("\n- set: epsilon\n  value: {}\n", e)),
+        Epsilon::Auto => out.push_str("\n- set: epsilon\n  value: auto\n"),
+    }
+
+    for l in world.lights() {
+        let (pos, intensity) = (l.pos(), l.intensity());
+        out.push_str("\n- add: light\n");
+        out.push_str(&format!("  at: {}\n", list(&[pos.0, pos.1, pos.2])));
+        out.push_str(&format!("  intensity: {}\n", list(&[intensity.0, intensity.1, intensity.2])));
+    }
+
+    for obj in world.objects() {
+        match obj.shape() {
+            Shape::Sphere(_) => out.push_str("\n- add: sphere\n"),
+        }
+        if let Some(name) = obj.name() {
+            out.push_str(&format!("  name: {}\n", quote(name)));
+        }
+        material_block(&mut out, &obj.material);
+        if *obj.transform().matrix() != Matrix4::identity() {
+            transform_block(&mut out, "transform", obj.transform().matrix());
+        }
+        if let Some(motion) = obj.motion() {
+            transform_block(&mut out, "end-transform", motion.end_transform());
+        }
+    }
+    out
+}
+
+pub fn save_file<P: AsRef<Path>>(path: P, world: &World, camera: &Camera) -> io::Result<()> {
+    std::fs::write(path, save(world, camera))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULT_WORLD: &str = "
+- add: camera
+  width: 11
+  height: 11
+  field-of-view: 1.5707964
+  from: [0, 0, -5]
+  to: [0, 0, 0]
+  up: [0, 1, 0]
+
+- add: light
+  at: [-10, 10, -10]
+  intensity: [1, 1, 1]
+
+- define: base
+  value:
+    color: [0.8, 1.0, 0.6]
+    diffuse: 0.7
+
+- define: outer
+  extend: base
+  value:
+    specular: 0.2
+
+- define: half
+  value:
+    - [scale, 0.5, 1, 0.5]
+    - [scale, 1, 0.5, 1]
+
+- add: sphere
+  material: outer
+
+- add: sphere
+  transform:
+    - half
+";
+
+    #[test]
+    fn test_load() {
+        let scene = load(DEFAULT_WORLD).unwrap();
+        let w = World::new_default();
+        let mut c = Camera::new(11.0, 11.0, crate::consts::PI / 2.0);
+        c.set_view(
+            Point3::new(0.0, 0.0, -5.0),
+            Point3::origin(),
+            Vector3::new(0.0, 1.0, 0.0),
+        ).unwrap();
+
+        assert!(scene.camera.width() == 11);
+        for &(x, y) in &[(5, 5), (3, 4), (0, 0)] {
+            assert!(scene.camera.render_pixel(&scene.world, x, y) == c.render_pixel(&w, x, y));
+        }
+    }
+
+    #[test]
+    fn test_transform_ops() {
+        let src = format!("{}{}", DEFAULT_WORLD, "
+- add: sphere
+  transform:
+    - [ shear, 1, 0, 0, 0, 0, 0.5 ]
+    - [ rotate, 0, 2, 0, 0.5 ]
+    - [ reflect, 0, 1, 0, 0, 1, 1 ]
+    - [ look-at, 1, 2, 3, 0, 0, 0, 0, 1, 0 ]
+");
+        let scene = load(&src).unwrap();
+        let up = Tuple::new_vector(0.0, 1.0, 0.0);
+        let m = TransformBuilder::identity()
+            .shear(1.0, 0.0, 0.0, 0.0, 0.0, 0.5)
+            .rotation_y(0.5)
+            .reflect(Tuple::new_point(0.0, 1.0, 0.0), Tuple::new_vector(0.0, 1.0, 1.0))
+            .look_at(Tuple::new_point(1.0, 2.0, 3.0), Tuple::new_point(0.0, 0.0, 0.0), up)
+            .build();
+        assert!(scene.world.objects()[2].transform().matrix().eq_real(m));
+
+        let e = load(&src.replace("[ rotate, 0, 2, 0,", "[ rotate, 0, 0, 0,")).err().unwrap();
+        assert!(e.line == 39 && e.message.contains("'rotate'"));
+        let e = load(&src.replace("0, 0, 0, 0, 1, 0 ]", "0, 0, 0, 1, 2, 3 ]")).err().unwrap();
+        assert!(e.line == 41 && e.message.contains("'look-at'"));
+
+        // defines that end up using themselves
+        let cycle = format!("{}{}", DEFAULT_WORLD, "
+- define: a
+  value: [ b ]
+- define: b
+  value: [ [ scale, 1, 2, 1 ], a ]
+- add: sphere
+  transform: [ a ]
+");
+        let e = load(&cycle).err().unwrap();
+        assert!(e.line == 39 && e.message.contains("'a' refers to itself"));
+        let e = load(&cycle.replace("value: [ b ]", "value: [ a ]")).err().unwrap();
+        assert!(e.line == 37 && e.message.contains("'a'"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let e = load("- add: camera\n  width: 10\n").err().unwrap();
+        assert!(e.line == 1);
+
+        let src = DEFAULT_WORLD.replace("- [scale, 1, 0.5, 1]", "- [scale, 1, 0, 1]");
+        let e = load(&src).err().unwrap();
+        assert!(e.line == 34 && e.message.contains("inverted"));
+
+        // errors about an object use its name, wherever it is in the item
+        let src = DEFAULT_WORLD.replace("    - half\n", "    - [scale, 0, 1, 1]\n  name: inner\n");
+        let e = load(&src).err().unwrap();
+        assert!(e.line == 34 && e.message.contains("'inner'"));
+
+        let src = DEFAULT_WORLD.replace("to: [0, 0, 0]", "to: [0, 0, -5]");
+        assert!(load(&src).err().unwrap().line == 2);
+
+        let src = DEFAULT_WORLD.replace("specular: 0.2", "specular: shiny");
+        let e = load(&src).err().unwrap();
+        assert!(e.line == 22 && e.message.contains("shiny"));
+
+        let src = DEFAULT_WORLD.replace("specular: 0.2", "casts-shadow: no");
+        let e = load(&src).err().unwrap();
+        assert!(e.line == 22 && e.message.contains("true or false"));
+
+        let src = DEFAULT_WORLD.replace("material: outer", "material: inner");
+        assert!(load(&src).err().unwrap().line == 30);
+
+        let src = format!("{}\n- set: epsilon\n  value: tiny\n", DEFAULT_WORLD);
+        assert!(load(&src).err().unwrap().line == 37);
+        for value in &["0", "-0.01", "NaN", "inf"] {
+            let src = format!("{}\n- set: epsilon\n  value: {}\n", DEFAULT_WORLD, value);
+            let e = load(&src).err().unwrap();
+            assert!(e.line == 37 && e.message.contains("positive"));
+        }
+        let src = format!("{}\n- set: acne\n  value: 0.1\n", DEFAULT_WORLD);
+        assert!(load(&src).err().unwrap().message.contains("'acne'"));
+
+        let e = load("- add: light\n").err().unwrap();
+        assert!(e.message.contains("no camera"));
+    }
+
+    #[test]
+    fn test_save() {
+        let mut w = World::new_default();
+        w.add_light(PointLight::new(Color::new(0.1, 0.7, 1.0 / 3.0), Tuple::new_point(1e-7, 2.5, -1e8)));
+        let mut s = Object::new(Sphere::new());
+        s.apply_transform(
+            TransformBuilder::identity()
+                .scale(0.3, 1.0, 2.0)
+                .rotation_x(1.0)
+                .rotation_z(-0.25)
+                .translate(1.0 / 7.0, 0.0, 3.0)
+                .build()
+        ).unwrap();
+        s.material.shininess = 10.0;
+        s.material.color = Color::new(0.1, 0.2, 0.3);
+        w.add_object(s);
+        let mut moving = Object::new(Sphere::new());
+        moving.set_name("moving \"ball\"\\1");
+        moving.apply_motion(Matrix4::rotation_y(0.1), Matrix4::translate(0.0, 0.0, 2.0)).unwrap();
+        w.add_object(moving);
+        let mut clean = Object::new(Sphere::new());
+        clean.apply_transform(Matrix4::translate(1.0, 2.0, 3.0) * Matrix4::scale(0.5, 2.0, 1.0)).unwrap();
+        clean.material.transparency = 0.9;
+        clean.material.casts_shadow = false;
+        w.add_object(clean);
+        w.set_epsilon(Epsilon::Auto);
+
+        let mut c = Camera::new(160.0, 90.0, 1.2);
+        c.set_view(
+            Point3::new(1.0, 2.0, -5.0),
+            Point3::new(0.1, 0.3, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ).unwrap();
+        c.aperture_radius = 0.05;
+        c.focal_distance = 5.3;
+        c.shutter = (0.0, 0.5);
+        c.projection = Projection::Orthographic(4.5);
+
+        let src = save(&w, &c);
+        let scene = load(&src).unwrap();
+        assert!(scene.world.objects() == w.objects());
+        assert!(scene.camera.transform().matrix() == c.transform().matrix());
+        assert!(scene.camera.projection == c.projection);
+        assert!(scene.world.epsilon() == Epsilon::Auto);
+        assert!(save(&scene.world, &scene.camera) == src);
+
+        // written as steps when they give back the same matrix
+        assert!(src.contains("    - [ scale, 0.5, 2, 1 ]\n    - [ translate, 1, 2, 3 ]\n"));
+        assert!(src.contains("  end-transform:\n    - [ translate, 0, 0, 2 ]\n"));
+
+        let q = Quaternion::from_axis_angle(Tuple::new_vector(1.0, 1.0, 0.0), 0.5);
+        let src = format!("{}\n- add: sphere\n  transform:\n    - [ quaternion, {}, {}, {}, {} ]\n", DEFAULT_WORLD, q.w, q.x, q.y, q.z);
+        let scene = load(&src).unwrap();
+        assert!(*scene.world.objects()[2].transform().matrix() == TransformBuilder::identity().rotate(&q).build());
+
+        // escaped quotes don't end the string for comments and brackets
+        let mut w = World::new();
+        for name in &["a\" #b", "x\" [y", "c\" {z", "\\\" ]#'"] {
+            let mut s = Object::new(Sphere::new());
+            s.set_name(name);
+            w.add_object(s);
+        }
+        let scene = load(&save(&w, &c)).unwrap();
+        let names = scene.world.objects().iter().map(|o| o.name().unwrap()).collect::<Vec<_>>();
+        assert!(names == ["a\" #b", "x\" [y", "c\" {z", "\\\" ]#'"]);
+    }
+
+    #[test]
+    fn test_load_file() {
+        let scene = load_file(concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/sphere_scene.yml")).unwrap();
+        assert!(scene.camera.width() == 2000 && scene.camera.height() == 1000);
+        assert!(load_file("no/such/scene.yml").err().unwrap().line == 0);
+    }
+}
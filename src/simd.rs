@@ -0,0 +1,303 @@
+// four Floats worked on together, in one SSE register for f32 on x86_64
+// and as a plain array elsewhere, with f64, or when built with the scalar feature
+use std::ops;
+use crate::Float;
+
+#[cfg(all(target_arch = "x86_64", not(feature = "f64"), not(feature = "scalar")))]
+pub use self::sse::F4;
+#[cfg(not(all(target_arch = "x86_64", not(feature = "f64"), not(feature = "scalar"))))]
+pub use self::scalar::F4;
+
+// what the ray packets need from their lanes, so they can be traced with
+// either kind of F4 and the two compared in one build
+pub trait Lanes: Copy + ops::Add<Output = Self> + ops::Sub<Output = Self> + ops::Mul<Output = Self> + ops::Div<Output = Self> {
+    fn new(a: Float, b: Float, c: Float, d: Float) -> Self;
+    fn splat(v: Float) -> Self;
+    fn from_array(a: &[Float; 4]) -> Self;
+    fn to_array(self) -> [Float; 4];
+    fn sqrt(self) -> Self;
+}
+
+macro_rules! impl_lanes {
+    ($t:ty) => {
+        impl Lanes for $t {
+            #[inline]
+            fn new(a: Float, b: Float, c: Float, d: Float) -> Self { <$t>::new(a, b, c, d) }
+            #[inline]
+            fn splat(v: Float) -> Self { <$t>::splat(v) }
+            #[inline]
+            fn from_array(a: &[Float; 4]) -> Self { <$t>::from_array(a) }
+            #[inline]
+            fn to_array(self) -> [Float; 4] { <$t>::to_array(self) }
+            #[inline]
+            fn sqrt(self) -> Self { <$t>::sqrt(self) }
+        }
+    };
+}
+
+impl_lanes!(scalar::F4);
+#[cfg(all(target_arch = "x86_64", not(feature = "f64"), not(feature = "scalar")))]
+impl_lanes!(sse::F4);
+
+pub mod scalar {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct F4([Float; 4]);
+
+    impl F4 {
+        #[inline]
+        pub fn new(a: Float, b: Float, c: Float, d: Float) -> Self {
+            F4([a, b, c, d])
+        }
+
+        #[inline]
+        pub fn splat(v: Float) -> Self {
+            F4([v; 4])
+        }
+
+        #[inline]
+        pub fn from_array(a: &[Float; 4]) -> Self {
+            F4(*a)
+        }
+
+        #[inline]
+        pub fn to_array(self) -> [Float; 4] {
+            self.0
+        }
+
+        // ignores the fourth lane
+        #[inline]
+        pub fn dot3(self, other: F4) -> Float {
+            let (a, b) = (self.0, other.0);
+            a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+        }
+
+        // the fourth lane of the result is 0
+        #[inline]
+        pub fn cross3(self, other: F4) -> F4 {
+            let (a, b) = (self.0, other.0);
+            F4([
+                a[1] * b[2] - a[2] * b[1],
+                a[2] * b[0] - a[0] * b[2],
+                a[0] * b[1] - a[1] * b[0],
+                0.0,
+            ])
+        }
+
+        // NaN in the lanes that are negative
+        #[inline]
+        pub fn sqrt(self) -> F4 {
+            let a = self.0;
+            F4([a[0].sqrt(), a[1].sqrt(), a[2].sqrt(), a[3].sqrt()])
+        }
+
+        // m * v with m stored row by row, like Matrix4
+        #[inline]
+        pub fn mul_matrix(m: &[Float; 16], v: F4) -> F4 {
+            let row = |i: usize| F4([m[i * 4], m[i * 4 + 1], m[i * 4 + 2], m[i * 4 + 3]]);
+            let dot4 = |r: F4| r.dot3(v) + r.0[3] * v.0[3];
+            F4([dot4(row(0)), dot4(row(1)), dot4(row(2)), dot4(row(3))])
+        }
+
+        #[inline]
+        fn zip(self, other: F4, f: impl Fn(Float, Float) -> Float) -> F4 {
+            let (a, b) = (self.0, other.0);
+            F4([f(a[0], b[0]), f(a[1], b[1]), f(a[2], b[2]), f(a[3], b[3])])
+        }
+    }
+
+    impl ops::Add for F4 {
+        type Output = F4;
+
+        #[inline]
+        fn add(self, other: F4) -> F4 {
+            self.zip(other, |a, b| a + b)
+        }
+    }
+
+    impl ops::Sub for F4 {
+        type Output = F4;
+
+        #[inline]
+        fn sub(self, other: F4) -> F4 {
+            self.zip(other, |a, b| a - b)
+        }
+    }
+
+    impl ops::Mul for F4 {
+        type Output = F4;
+
+        #[inline]
+        fn mul(self, other: F4) -> F4 {
+            self.zip(other, |a, b| a * b)
+        }
+    }
+
+    impl ops::Div for F4 {
+        type Output = F4;
+
+        #[inline]
+        fn div(self, other: F4) -> F4 {
+            self.zip(other, |a, b| a / b)
+        }
+    }
+}
+
+// SSE and SSE2 are part of every x86_64 cpu, so the intrinsics are always safe to call
+// and there is no runtime check, the loads only read through valid references
+#[cfg(all(target_arch = "x86_64", not(feature = "f64"), not(feature = "scalar")))]
+mod sse {
+    use super::*;
+    use std::arch::x86_64::*;
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct F4(__m128);
+
+    impl F4 {
+        #[inline]
+        pub fn new(a: f32, b: f32, c: f32, d: f32) -> Self {
+            unsafe { F4(_mm_set_ps(d, c, b, a)) }
+        }
+
+        #[inline]
+        pub fn splat(v: f32) -> Self {
+            unsafe { F4(_mm_set1_ps(v)) }
+        }
+
+        #[inline]
+        pub fn from_array(a: &[f32; 4]) -> Self {
+            unsafe { F4(_mm_loadu_ps(a.as_ptr())) }
+        }
+
+        #[inline]
+        pub fn to_array(self) -> [f32; 4] {
+            let mut out = [0.0; 4];
+            unsafe { _mm_storeu_ps(out.as_mut_ptr(), self.0) };
+            out
+        }
+
+        #[inline]
+        pub fn dot3(self, other: F4) -> f32 {
+            unsafe {
+                let p = _mm_mul_ps(self.0, other.0);
+                let y = _mm_shuffle_ps(p, p, 0b01_01_01_01);
+                let z = _mm_shuffle_ps(p, p, 0b10_10_10_10);
+                _mm_cvtss_f32(_mm_add_ss(_mm_add_ss(p, y), z))
+            }
+        }
+
+        #[inline]
+        pub fn cross3(self, other: F4) -> F4 {
+            // (y, z, x, w) and (z, x, y, w)
+            let (a, b) = (self.0, other.0);
+            unsafe {
+                let a_yzx = _mm_shuffle_ps(a, a, 0b11_00_10_01);
+                let a_zxy = _mm_shuffle_ps(a, a, 0b11_01_00_10);
+                let b_yzx = _mm_shuffle_ps(b, b, 0b11_00_10_01);
+                let b_zxy = _mm_shuffle_ps(b, b, 0b11_01_00_10);
+                let c = _mm_sub_ps(_mm_mul_ps(a_yzx, b_zxy), _mm_mul_ps(a_zxy, b_yzx));
+                // a.w * b.w - a.w * b.w is only 0 for finite w
+                F4(_mm_and_ps(c, _mm_castsi128_ps(_mm_set_epi32(0, -1, -1, -1))))
+            }
+        }
+
+        #[inline]
+        pub fn sqrt(self) -> F4 {
+            unsafe { F4(_mm_sqrt_ps(self.0)) }
+        }
+
+        #[inline]
+        pub fn mul_matrix(m: &[f32; 16], v: F4) -> F4 {
+            unsafe {
+                let row = |i: usize| _mm_loadu_ps(m[i * 4..].as_ptr());
+                let p0 = _mm_mul_ps(row(0), v.0);
+                let p1 = _mm_mul_ps(row(1), v.0);
+                let p2 = _mm_mul_ps(row(2), v.0);
+                let p3 = _mm_mul_ps(row(3), v.0);
+                // transpose so each lane sums one row
+                let s01 = _mm_add_ps(_mm_unpacklo_ps(p0, p1), _mm_unpackhi_ps(p0, p1));
+                let s23 = _mm_add_ps(_mm_unpacklo_ps(p2, p3), _mm_unpackhi_ps(p2, p3));
+                F4(_mm_add_ps(_mm_movelh_ps(s01, s23), _mm_movehl_ps(s23, s01)))
+            }
+        }
+    }
+
+    impl PartialEq for F4 {
+        fn eq(&self, other: &F4) -> bool {
+            self.to_array() == other.to_array()
+        }
+    }
+
+    impl ops::Add for F4 {
+        type Output = F4;
+
+        #[inline]
+        fn add(self, other: F4) -> F4 {
+            unsafe { F4(_mm_add_ps(self.0, other.0)) }
+        }
+    }
+
+    impl ops::Sub for F4 {
+        type Output = F4;
+
+        #[inline]
+        fn sub(self, other: F4) -> F4 {
+            unsafe { F4(_mm_sub_ps(self.0, other.0)) }
+        }
+    }
+
+    impl ops::Mul for F4 {
+        type Output = F4;
+
+        #[inline]
+        fn mul(self, other: F4) -> F4 {
+            unsafe { F4(_mm_mul_ps(self.0, other.0)) }
+        }
+    }
+
+    impl ops::Div for F4 {
+        type Output = F4;
+
+        #[inline]
+        fn div(self, other: F4) -> F4 {
+            unsafe { F4(_mm_div_ps(self.0, other.0)) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // whichever F4 is in use has to agree with the scalar one
+    #[test]
+    fn test_f4() {
+        let (a, b) = ([1.0, -2.0, 3.0, 0.5], [4.0, 0.25, -1.0, 2.0]);
+        let (v, w) = (F4::from_array(&a), F4::from_array(&b));
+        let (sv, sw) = (scalar::F4::from_array(&a), scalar::F4::from_array(&b));
+
+        assert!(v.to_array() == a);
+        assert!((v + w).to_array() == (sv + sw).to_array());
+        assert!((v - w).to_array() == (sv - sw).to_array());
+        assert!((v * w).to_array() == (sv * sw).to_array());
+        assert!((v / w).to_array() == (sv / sw).to_array());
+        assert!(F4::new(4.0, 2.25, 0.0, 1.0).sqrt().to_array() == [2.0, 1.5, 0.0, 1.0]);
+        assert!(v.sqrt().to_array()[1].is_nan());
+        assert!((F4::splat(2.0) * v).to_array() == [2.0, -4.0, 6.0, 1.0]);
+        assert!(v.dot3(w) == 0.5);
+        assert!(v.dot3(w) == sv.dot3(sw));
+        assert!(v.cross3(w).to_array() == sv.cross3(sw).to_array());
+        assert!(v.cross3(w).to_array() == [1.25, 13.0, 8.25, 0.0]);
+
+        let m = [
+            1.0, 2.0, 3.0, 4.0,
+            5.0, 6.0, 7.0, 8.0,
+            9.0, 8.0, 7.0, 6.0,
+            5.0, 4.0, 3.0, 2.0,
+        ];
+        let p = F4::new(1.0, 2.0, 3.0, 1.0);
+        assert!(F4::mul_matrix(&m, p).to_array() == [18.0, 46.0, 52.0, 24.0]);
+        assert!(F4::mul_matrix(&m, p).to_array() == scalar::F4::mul_matrix(&m, scalar::F4::new(1.0, 2.0, 3.0, 1.0)).to_array());
+    }
+}
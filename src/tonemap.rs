@@ -0,0 +1,108 @@
+use crate::color::Color;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMap {
+    // cuts everything above 1.0, what the writers always did
+    Clamp,
+    // 1 - e^-x
+    Exposure,
+    // x / (1 + x)
+    Reinhard,
+    // Narkowicz's fit of the ACES filmic curve
+    AcesFilmic,
+}
+
+impl ToneMap {
+    pub fn apply(&self, x: f32) -> f32 {
+        let x = x.max(0.0);
+        match self {
+            ToneMap::Clamp => x.min(1.0),
+            ToneMap::Exposure => 1.0 - (-x).exp(),
+            ToneMap::Reinhard => x / (1.0 + x),
+            ToneMap::AcesFilmic => {
+                let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+                ((x * (a * x + b)) / (x * (c * x + d) + e)).min(1.0)
+            },
+        }
+    }
+}
+
+pub fn srgb_encode(x: f32) -> f32 {
+    if x <= 0.0031308 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_decode(x: f32) -> f32 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// turns the linear radiance in a Canvas into display values in [0, 1]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DisplayTransform {
+    // in stops, every stop doubles the brightness
+    pub exposure: f32,
+    pub tone_map: ToneMap,
+    pub srgb: bool,
+}
+
+impl Default for DisplayTransform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DisplayTransform {
+    // linear and clamped, leaves the values as they are
+    pub fn new() -> Self {
+        Self { exposure: 0.0, tone_map: ToneMap::Clamp, srgb: false }
+    }
+
+    pub fn srgb(tone_map: ToneMap) -> Self {
+        Self { exposure: 0.0, tone_map, srgb: true }
+    }
+
+    pub fn apply(&self, c: Color<f32>) -> Color<f32> {
+        let scale = 2f32.powf(self.exposure);
+        c.fmap(|x| {
+            let x = self.tone_map.apply(x * scale);
+            if self.srgb { srgb_encode(x) } else { x }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tone_maps() {
+        for t in &[ToneMap::Clamp, ToneMap::Exposure, ToneMap::Reinhard, ToneMap::AcesFilmic] {
+            assert!(t.apply(0.0) == 0.0);
+            assert!(t.apply(-1.0) == 0.0);
+            assert!(t.apply(1000.0) <= 1.0);
+            assert!(t.apply(0.5) < t.apply(0.6));
+        }
+        assert!(ToneMap::Reinhard.apply(1.0) == 0.5);
+        assert!(ToneMap::Clamp.apply(2.0) == 1.0);
+    }
+
+    #[test]
+    fn test_srgb() {
+        assert!(srgb_encode(0.0) == 0.0);
+        assert!((srgb_encode(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_encode(0.214) - 0.5).abs() < 1e-3); // midtones get brighter
+        assert!((srgb_decode(srgb_encode(0.3)) - 0.3).abs() < 1e-6);
+
+        let mut d = DisplayTransform::srgb(ToneMap::Clamp);
+        d.exposure = 1.0;
+        assert!(d.apply(Color::new(0.5, 0.0, 4.0)) == Color::new(1.0, 0.0, 1.0));
+        assert!(DisplayTransform::new().apply(Color::new(0.25, 0.5, 2.0)) == Color::new(0.25, 0.5, 1.0));
+    }
+}
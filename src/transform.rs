@@ -0,0 +1,140 @@
+use crate::matrix4::Matrix4;
+use crate::geometry::{Point3, Vector3, Normal3};
+use crate::ray::Ray;
+use crate::bounds::Bounds;
+use crate::Float;
+
+// a matrix together with its inverse and inverse transpose, all computed once,
+// maps from the local space of an object or the camera to world space
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4<Float>,
+    inverse: Matrix4<Float>,
+    inv_transpose: Matrix4<Float>,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        let m = Matrix4::identity();
+        Self { matrix: m, inverse: m, inv_transpose: m }
+    }
+
+    // None if the matrix can't be inverted
+    pub fn new(matrix: Matrix4<Float>) -> Option<Self> {
+        let inverse = matrix.inverse()?;
+        Some(Self { matrix, inverse, inv_transpose: inverse.transpose() })
+    }
+
+    // for callers that already know the inverse, it isn't checked
+    pub fn from_inverse(matrix: Matrix4<Float>, inverse: Matrix4<Float>) -> Self {
+        Self { matrix, inverse, inv_transpose: inverse.transpose() }
+    }
+
+    pub fn matrix(&self) -> &Matrix4<Float> {
+        &self.matrix
+    }
+
+    pub fn inverse_matrix(&self) -> &Matrix4<Float> {
+        &self.inverse
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Self { matrix: self.inverse, inverse: self.matrix, inv_transpose: self.matrix.transpose() }
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        self.matrix * p
+    }
+
+    pub fn vector(&self, v: Vector3) -> Vector3 {
+        self.matrix * v
+    }
+
+    // normals go through the inverse transpose to stay perpendicular to the surface
+    pub fn normal(&self, n: Normal3) -> Normal3 {
+        Normal3::new(self.inv_transpose * n.to_vector())
+    }
+
+    // the direction is not normalized, so t stays the same in both spaces
+    pub fn ray(&self, r: &Ray) -> Ray {
+        r.transform(&self.matrix)
+    }
+
+    // the inverse direction, from world space to local space
+    pub fn inv_point(&self, p: Point3) -> Point3 {
+        self.inverse * p
+    }
+
+    pub fn inv_ray(&self, r: &Ray) -> Ray {
+        r.transform(&self.inverse)
+    }
+
+    // how much longer a vector can get, the longest of the transformed axes
+    pub fn max_scale(&self) -> Float {
+        let axis = |v| self.vector(v).magnitude();
+        axis(Vector3::new(1.0, 0.0, 0.0))
+            .max(axis(Vector3::new(0.0, 1.0, 0.0)))
+            .max(axis(Vector3::new(0.0, 0.0, 1.0)))
+    }
+
+    // the box around the transformed corners, which can be larger than the tightest fit
+    pub fn bounds(&self, b: &Bounds) -> Bounds {
+        let mut out = Bounds::empty();
+        if !b.is_empty() {
+            for &c in b.corners().iter() {
+                out.add_point(self.point(c));
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::TransformBuilder;
+
+    #[test]
+    fn test_transform() {
+        let m = TransformBuilder::identity()
+            .scale(2.0, 1.0, 1.0)
+            .rotation_z(crate::consts::PI / 2.0)
+            .translate(0.0, 0.0, 3.0)
+            .build();
+        let t = Transform::new(m).unwrap();
+
+        let p = t.point(Point3::new(1.0, 0.0, 0.0));
+        assert!(p.eq_real(&Point3::new(0.0, 2.0, 3.0)));
+        assert!(t.inv_point(p).eq_real(&Point3::new(1.0, 0.0, 0.0)));
+        assert!(t.vector(Vector3::new(0.0, 1.0, 0.0)).eq_real(&Vector3::new(-1.0, 0.0, 0.0)));
+
+        // the normal of the plane x + y = 1 before the transform
+        let n = t.normal(Normal3::new(Vector3::new(1.0, 1.0, 0.0)));
+        let h = (2.0 as Float).sqrt() / 2.0;
+        assert!((n.to_vector() - Vector3::new(-2.0 / (5.0 as Float).sqrt(), 1.0 / (5.0 as Float).sqrt(), 0.0)).magnitude() < 1e-5);
+        assert!(t.inverse().normal(n).to_vector().eq_real(&Vector3::new(h, h, 0.0)));
+
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        let wr = t.ray(&r);
+        assert!(wr.dir.eq_real(&Vector3::new(0.0, 2.0, 0.0)));
+        assert!(t.inv_ray(&wr).origin.eq_real(&r.origin));
+
+        assert!((t.max_scale() - 2.0).abs() < 1e-5);
+
+        assert!(Transform::new(Matrix4::scale(1.0, 0.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn test_bounds() {
+        let unit = Bounds::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+        let t = Transform::new(Matrix4::translate(1.0, 0.0, 0.0) * Matrix4::scale(2.0, 1.0, 1.0)).unwrap();
+        let b = t.bounds(&unit);
+        assert!(b == Bounds::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(3.0, 1.0, 1.0)));
+
+        let t = Transform::new(Matrix4::rotation_y(crate::consts::PI / 4.0)).unwrap();
+        let b = t.bounds(&unit);
+        let r = (2.0 as Float).sqrt();
+        assert!((b.max.x - r).abs() < 1e-5 && (b.min.z + r).abs() < 1e-5);
+        assert!(t.bounds(&Bounds::empty()).is_empty());
+    }
+}
@@ -0,0 +1,117 @@
+use crate::matrix4::Matrix4;
+use crate::quaternion::Quaternion;
+use crate::transform::Transform;
+use crate::tuple::Tuple;
+use crate::Float;
+
+// how far to_matrix can be from the decomposed matrix, relative to its largest element
+const DECOMPOSE_TOLERANCE: Float = 1e-4;
+
+// a transform split into translation, rotation and scale,
+// it is translate * rotate * scale, so the scale is applied first
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Trs {
+    pub translation: Tuple<Float>,
+    pub rotation: Quaternion,
+    pub scale: Tuple<Float>,
+}
+
+impl Trs {
+    pub fn new(translation: Tuple<Float>, rotation: Quaternion, scale: Tuple<Float>) -> Self {
+        Self { translation, rotation, scale }
+    }
+
+    // None if m can't be inverted, isn't affine or isn't translate * rotate * scale,
+    // like with a shear or a scale after a rotation, a mirror ends up as a negative x scale
+    pub fn decompose(m: &Matrix4<Float>) -> Option<Self> {
+        if m[(3, 0)] != 0.0 || m[(3, 1)] != 0.0 || m[(3, 2)] != 0.0 || m[(3, 3)] != 1.0 {
+            return None
+        }
+        m.inverse()?;
+
+        let translation = Tuple::new_vector(m[(0, 3)], m[(1, 3)], m[(2, 3)]);
+
+        let col = |j: usize| Tuple::new_vector(m[(0, j)], m[(1, j)], m[(2, j)]);
+        let mut scale = Tuple::new_vector(col(0).magnitude(), col(1).magnitude(), col(2).magnitude());
+        if m.det() < 0.0 {
+            // a mirror, put it into the scale so the rest is a rotation
+            scale.0 = -scale.0;
+        }
+
+        let rotation = Matrix4::new_fn(|i, j| {
+            if i < 3 && j < 3 { m[(i, j)] / scale[j] } else if i == j { 1.0 } else { 0.0 }
+        });
+
+        let trs = Self { translation, rotation: Quaternion::from_rotation_matrix(&rotation), scale };
+        // what isn't a rotation and a scale along the axes is left over, the
+        // rounding of the quaternion grows with the size of the matrix
+        let size = (0..9).map(|k| m[(k / 3, k % 3)].abs()).fold(0.0, Float::max);
+        if !trs.to_matrix().eq_within(*m, DECOMPOSE_TOLERANCE * size) {
+            return None
+        }
+        Some(trs)
+    }
+
+    pub fn to_matrix(&self) -> Matrix4<Float> {
+        let (tr, sc) = (self.translation, self.scale);
+        Matrix4::translate(tr.0, tr.1, tr.2) * self.rotation.to_matrix() * Matrix4::scale(sc.0, sc.1, sc.2)
+    }
+
+    // to_matrix and its inverse, which is scale^-1 * rotation^T * translate^-1,
+    // so there is no general 4x4 inverse, the scale can't have a 0 in it
+    pub fn to_transform(&self) -> Transform {
+        let (tr, sc) = (self.translation, self.scale);
+        let rotation = self.rotation.to_matrix();
+        let matrix = Matrix4::translate(tr.0, tr.1, tr.2) * rotation * Matrix4::scale(sc.0, sc.1, sc.2);
+        let inverse = Matrix4::scale(1.0 / sc.0, 1.0 / sc.1, 1.0 / sc.2) * rotation.transpose() * Matrix4::translate(-tr.0, -tr.1, -tr.2);
+        Transform::from_inverse(matrix, inverse)
+    }
+
+    // translation and scale move in a straight line, the rotation along the shortest arc
+    pub fn lerp(&self, other: &Trs, t: Float) -> Trs {
+        Self {
+            translation: self.translation * (1.0 - t) + other.translation * t,
+            rotation: self.rotation.slerp(&other.rotation, t),
+            scale: self.scale * (1.0 - t) + other.scale * t,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::TransformBuilder;
+
+    #[test]
+    fn test_decompose() {
+        let axis = Tuple::new_vector(1.0, 2.0, -1.0);
+        let m = TransformBuilder::identity()
+            .scale(2.0, 0.5, 3.0)
+            .rotate(&Quaternion::from_axis_angle(axis, 0.7))
+            .translate(1.0, -2.0, 4.0)
+            .build();
+        let trs = Trs::decompose(&m).unwrap();
+        assert!(trs.translation == Tuple::new_vector(1.0, -2.0, 4.0));
+        assert!((trs.scale - Tuple::new_vector(2.0, 0.5, 3.0)).magnitude() < 1e-5);
+        assert!(trs.rotation.dot(&Quaternion::from_axis_angle(axis, 0.7)).abs() > 1.0 - 1e-5);
+        assert!(trs.to_matrix().eq_within(m, 1e-5));
+
+        let mirror = Matrix4::scale(-1.0, 1.0, 1.0) * Matrix4::rotation_z(0.3);
+        let trs = Trs::decompose(&mirror).unwrap();
+        assert!(trs.scale.0 < 0.0);
+        assert!(trs.to_matrix().eq_within(mirror, 1e-5));
+
+        assert!(Trs::decompose(&Matrix4::scale(1.0, 0.0, 1.0)).is_none());
+        // there is no translate * rotate * scale for these
+        let shear = Matrix4::shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        assert!(Trs::decompose(&shear).is_none());
+        let stretched = Matrix4::scale(2.0, 1.0, 1.0) * Matrix4::rotation_z(crate::consts::PI / 4.0);
+        assert!(Trs::decompose(&stretched).is_none());
+        let small = Matrix4::translate(1e3, 0.0, 0.0) * Matrix4::rotation_x(0.3) * Matrix4::scale(1e-3, 2e-3, 1e-3);
+        assert!(Trs::decompose(&small).unwrap().to_matrix().eq_within(small, 1e-6));
+
+        let mut projective = Matrix4::identity();
+        projective[(3, 2)] = 1.0;
+        assert!(Trs::decompose(&projective).is_none());
+    }
+}
@@ -1,29 +1,72 @@
 use crate::object::Hittable;
-use crate::object::find_hit;
 use crate::object::Hitrecord;
 use crate::object::Object;
 use crate::light::PointLight;
 use crate::ray::Ray;
+use crate::ray::RayPacket;
+use crate::simd::Lanes;
 use crate::tuple::Tuple;
-use crate::tuple::dot;
+use crate::geometry::{Point3, Vector3, Normal3};
 use crate::color::Color;
 use crate::object::Sphere;
-use crate::matrix::Matrix;
-use crate::material::lightning;
+use crate::matrix4::Matrix4;
+use crate::material::lightning_filtered;
+use crate::Float;
+
+// how far rays that start on a surface, like shadow rays, are moved off it
+// so they don't hit the surface they start from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Epsilon {
+    Fixed(Float),
+    // grows with the rounding errors, which are relative to the distance
+    // from the ray origin, the distance from the world origin and the object size
+    Auto,
+}
+
+// what every World starts with
+pub const DEFAULT_EPSILON: Epsilon = Epsilon::Fixed(0.01);
+
+// Auto is this many times the rounding error of a Float
+const AUTO_ULPS: Float = 1024.0;
+
+impl Epsilon {
+    pub fn offset(&self, t: Float, point: Point3, object_size: Float) -> Float {
+        match *self {
+            Epsilon::Fixed(e) => e,
+            Epsilon::Auto => {
+                let extent = point.x.abs().max(point.y.abs()).max(point.z.abs());
+                AUTO_ULPS * Float::EPSILON * t.abs().max(extent).max(object_size)
+            },
+        }
+    }
+}
 
 pub struct World {
-    lights: Vec<PointLight<f32>>,
+    lights: Vec<PointLight<Float>>,
     objects: Vec<Object>,
+    epsilon: Epsilon,
 }
 
 pub struct Hitinfo<'a> {
-    pub hit: f32,
+    pub hit: Float,
     pub obj: &'a Object,
-    pub point: Tuple<f32>,
-    pub over_point: Tuple<f32>,
-    pub eyev: Tuple<f32>,
-    pub normalv: Tuple<f32>,
+    pub point: Point3,
+    pub over_point: Point3,
+    pub eyev: Vector3,
+    pub normalv: Normal3,
     pub inside: bool,
+    pub time: Float,
+    // from World::epsilon
+    pub offset: Float,
+}
+
+impl<'a> Hitinfo<'a> {
+    // the point moved off the surface to the side dir leaves from,
+    // where rays going in dir start, like reflected and refracted ones
+    pub fn origin_towards(&self, dir: Vector3) -> Point3 {
+        let side = if self.normalv.dot(dir) >= 0.0 { self.normalv } else { -self.normalv };
+        self.point + side * self.offset
+    }
 }
 
 impl World {
@@ -31,6 +74,7 @@ impl World {
         Self {
             lights: Vec::new(),
             objects: Vec::new(),
+            epsilon: DEFAULT_EPSILON,
         }
     }
 
@@ -43,15 +87,16 @@ impl World {
         s1.material.specular = 0.2;
 
         let mut s2 = Object::new(Sphere::new());
-        s2.apply_transform(Matrix::scale(0.5, 0.5, 0.5));
+        s2.apply_transform(Matrix4::scale(0.5, 0.5, 0.5)).unwrap();
 
         Self {
             lights: vec![light],
             objects: vec![s1, s2],
+            epsilon: DEFAULT_EPSILON,
         }
     }
 
-    pub fn add_light(&mut self, l: PointLight<f32>) {
+    pub fn add_light(&mut self, l: PointLight<Float>) {
         self.lights.push(l);
     }
 
@@ -59,78 +104,216 @@ impl World {
         self.objects.push(obj);
     }
 
+    pub fn lights(&self) -> &[PointLight<Float>] {
+        &self.lights
+    }
+
+    pub fn objects(&self) -> &[Object] {
+        &self.objects
+    }
+
+    pub fn set_epsilon(&mut self, epsilon: Epsilon) {
+        self.epsilon = epsilon;
+    }
+
+    pub fn epsilon(&self) -> Epsilon {
+        self.epsilon
+    }
+
     pub fn intersect_world<'a>(&'a self, ray: &Ray) -> Vec<Hitrecord<'a>> {
-        let mut v = self.objects.iter()
-            .flat_map(|obj| {
-                obj.hit(ray)
-            })
-            .collect::<Vec<Hitrecord<'a>>>();
-        v.sort_by(|obj1, obj2| obj1.hit.partial_cmp(&obj2.hit).unwrap_or(std::cmp::Ordering::Equal));
+        let mut v = Vec::new();
+        self.intersect_world_into(ray, &mut v);
         v
     }
 
-    pub fn is_shadowed(&self, point: &Tuple<f32>, light: &PointLight<f32>) -> bool {
-        let v = light.pos - *point;
+    // every hit sorted by t, for callers that need more than the nearest one,
+    // hits is cleared first so the same buffer can be used for every ray
+    pub fn intersect_world_into<'a>(&'a self, ray: &Ray, hits: &mut Vec<Hitrecord<'a>>) {
+        hits.clear();
+        for obj in &self.objects {
+            obj.hit_into(ray, hits);
+        }
+        hits.sort_unstable_by(|obj1, obj2| obj1.hit.partial_cmp(&obj2.hit).unwrap_or(std::cmp::Ordering::Equal));
+    }
+
+    // the nearest hit with 0 <= t < t_max, every object shortens t_max for the next one
+    pub fn closest_hit(&self, ray: &Ray, t_max: Float) -> Option<Hitrecord<'_>> {
+        let mut closest = None;
+        let mut t_max = t_max;
+        for obj in &self.objects {
+            if let Some(t) = obj.closest_hit(ray, t_max) {
+                t_max = t;
+                closest = Some(Hitrecord::new(t, obj));
+            }
+        }
+        closest
+    }
+
+    // whether anything is hit with 0 <= t < t_max, stops at the first hit
+    pub fn any_hit(&self, ray: &Ray, t_max: Float) -> bool {
+        self.objects.iter().any(|obj| obj.closest_hit(ray, t_max).is_some())
+    }
+
+    pub fn is_shadowed(&self, point: &Point3, light: &PointLight<Float>) -> bool {
+        self.is_shadowed_at(point, light, 0.0)
+    }
+
+    // shadow rays have to be shot at the same time as the ray that found the point,
+    // any object that casts a shadow counts, however transparent
+    pub fn is_shadowed_at(&self, point: &Point3, light: &PointLight<Float>, time: Float) -> bool {
+        let v = light.position() - *point;
         // must do square root because of t
         let dist = v.magnitude();
         let dir = v.normalize();
 
-        let r = Ray::new(*point, dir);
-        let i = self.intersect_world(&r);
-        if let Some(h) = find_hit(i) {
-            h.hit < dist
-        } else { false }
+        let r = Ray::new_at(*point, dir, time);
+        self.objects.iter().any(|obj| obj.material.casts_shadow && obj.closest_hit(&r, dist).is_some())
     }
 
-    pub fn prepare_computations<'a>(hr: &Hitrecord<'a>, ray: &Ray) -> Hitinfo<'a> {
+    // the part of the light that reaches point, every object in between filters it
+    // through its material, white when nothing is in the way
+    pub fn light_filter_at(&self, point: &Point3, light: &PointLight<Float>, time: Float) -> Color<Float> {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let v = light.position() - *point;
+        let dist = v.magnitude();
+        let r = Ray::new_at(*point, v.normalize(), time);
+
+        let mut filter = Color::new(1.0, 1.0, 1.0);
+        for obj in &self.objects {
+            if obj.material.casts_shadow && obj.closest_hit(&r, dist).is_some() {
+                filter = filter * obj.material.shadow_filter();
+                // nothing gets past an opaque object
+                if filter == black {
+                    return black
+                }
+            }
+        }
+        filter
+    }
+
+    pub fn prepare_computations<'a>(&self, hr: &Hitrecord<'a>, ray: &Ray) -> Hitinfo<'a> {
         let pt = ray.pos(hr.hit);
 
-        let normalv = hr.obj.normal_at(pt);
+        let normalv = hr.obj.normal_at_time(pt, ray.time);
         let eyev = -ray.dir;
-        let inside = dot(normalv, eyev) < 0.0;
+        let inside = normalv.dot(eyev) < 0.0;
+        let normalv = if inside { -normalv } else { normalv };
+        // t is in units of the ray direction, which is normalized
+        let offset = self.epsilon.offset(hr.hit, pt, hr.obj.transform().max_scale());
         Hitinfo {
             hit: hr.hit,
             obj: hr.obj,
             point: pt,
-            over_point: pt + normalv * (0.01),
+            over_point: pt + normalv * offset,
             eyev,
-            normalv: if inside { -normalv } else { normalv },
+            normalv,
             inside,
+            time: ray.time,
+            offset,
         }
     }
 
-    pub fn shade_hit<'a>(&self, comps: &Hitinfo<'a>) -> Color<f32> {
+    pub fn shade_hit<'a>(&self, comps: &Hitinfo<'a>) -> Color<Float> {
         self.lights.iter()
             .map(|l| {
-                let in_shadow = self.is_shadowed(&comps.over_point, &l);
-                lightning(&comps.obj.material, &l, &comps.over_point, &comps.eyev, &comps.normalv, in_shadow)
+                let filter = self.light_filter_at(&comps.over_point, &l, comps.time);
+                lightning_filtered(&comps.obj.material, &l, &comps.over_point, &comps.eyev, &comps.normalv, filter)
             })
             .fold(Color::new(0.0, 0.0, 0.0), |a, b| a + b)
     }
 
-    pub fn color_at(&self, ray: &Ray) -> Color<f32> {
-        let hrs = self.intersect_world(ray); // get closest one
-        if hrs.len() == 0 {
-            Color::new(0.0, 0.0, 0.0)
-        } else {
-            let hi = Self::prepare_computations(&hrs[0], ray);
-            self.shade_hit(&hi)
+    pub fn color_at(&self, ray: &Ray) -> Color<Float> {
+        self.shade_ray(ray).0
+    }
+
+    // color_at and what it shaded, for the passes besides the color
+    pub fn shade_ray(&self, ray: &Ray) -> (Color<Float>, Option<Hitinfo<'_>>) {
+        match self.closest_hit(ray, Float::INFINITY) {
+            Some(h) => {
+                let hi = self.prepare_computations(&h, ray);
+                (self.shade_hit(&hi), Some(hi))
+            },
+            None => (Color::new(0.0, 0.0, 0.0), None),
         }
     }
+
+    // the index of obj in objects(), None when it belongs to another world
+    pub fn object_index(&self, obj: &Object) -> Option<usize> {
+        self.objects.iter().position(|o| std::ptr::eq(o, obj))
+    }
+
+    // color_at for four rays, the objects are intersected with all of them at once
+    pub fn color_at_packet<L: Lanes>(&self, rays: &RayPacket<L>) -> [Color<Float>; 4] {
+        let mut closest: [Option<Hitrecord>; 4] = [None, None, None, None];
+        for obj in &self.objects {
+            let [near, far] = obj.hit_packet(rays);
+            let (near, far) = (near.to_array(), far.to_array());
+            for (i, c) in closest.iter_mut().enumerate() {
+                // NaN, a miss, fails every comparison
+                let t = if near[i] >= 0.0 { near[i] } else { far[i] };
+                if t >= 0.0 && c.as_ref().is_none_or(|h| t < h.hit) {
+                    *c = Some(Hitrecord::new(t, obj));
+                }
+            }
+        }
+
+        // shade_hit, with the shadow rays of every light in a packet too
+        let hits = [0, 1, 2, 3].map(|i| closest[i].as_ref().map(|h| self.prepare_computations(h, &rays.ray(i))));
+        let mut colors = [Color::new(0.0, 0.0, 0.0); 4];
+        for light in &self.lights {
+            let filters = self.light_filter_packet::<L>(&hits, light);
+            for (i, hit) in hits.iter().enumerate() {
+                if let Some(hi) = hit {
+                    colors[i] = colors[i] + lightning_filtered(&hi.obj.material, light, &hi.over_point, &hi.eyev, &hi.normalv, filters[i]);
+                }
+            }
+        }
+        colors
+    }
+
+    // light_filter_at for the points of four hits, lanes without a hit are left white
+    fn light_filter_packet<L: Lanes>(&self, hits: &[Option<Hitinfo>; 4], light: &PointLight<Float>) -> [Color<Float>; 4] {
+        let black = Color::new(0.0, 0.0, 0.0);
+        // the empty lanes go along at the time of the others so the packet
+        // can still be transformed once, their distance of 0 is never reached
+        let time = hits.iter().flatten().next().map_or(0.0, |hi| hi.time);
+        let to_light = |i: usize| hits[i].as_ref().map(|hi| (hi, light.position() - hi.over_point));
+        let dist = [0, 1, 2, 3].map(|i| to_light(i).map_or(0.0, |(_, v)| v.magnitude()));
+        let packet = RayPacket::<L>::new(&[0, 1, 2, 3].map(|i| match to_light(i) {
+            Some((hi, v)) => Ray::new_at(hi.over_point, v.normalize(), hi.time),
+            None => Ray::new_at(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), time),
+        }));
+
+        let mut filters = [Color::new(1.0, 1.0, 1.0); 4];
+        for obj in self.objects.iter().filter(|obj| obj.material.casts_shadow) {
+            let [near, far] = obj.hit_packet(&packet);
+            let (near, far) = (near.to_array(), far.to_array());
+            for (i, filter) in filters.iter_mut().enumerate() {
+                let blocked = (near[i] >= 0.0 && near[i] < dist[i]) || (far[i] >= 0.0 && far[i] < dist[i]);
+                if blocked && *filter != black {
+                    let f = *filter * obj.material.shadow_filter();
+                    // nothing gets past an opaque object
+                    *filter = if f == black { black } else { f };
+                }
+            }
+        }
+        filters
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::simd::{scalar, F4};
 
     #[test]
     fn test_intersect_world() {
         let w = World::new_default();
-        let r = Ray::new(Tuple::new_point(0.0, 0.0, -5.0), Tuple::new_vector(0.0, 0.0, 1.0));
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
         let xs = w.intersect_world(&r);
 
-        let hits: Vec<f32> = xs.iter().map(|x| x.hit).collect();
+        let hits: Vec<Float> = xs.iter().map(|x| x.hit).collect();
         println!("{:?}", hits);
 
         assert!(xs.len() == 4);
@@ -142,20 +325,45 @@ mod tests {
 
     #[test]
     fn test_prepare_computations() {
-        let r = Ray::new(Tuple::new_point(0.0, 0.0, -5.0), Tuple::new_vector(0.0, 0.0, 1.0));
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
         let s = Object::new(Sphere::new());
         let i = Hitrecord { hit: 4.0, obj: &s };
-        let comps = World::prepare_computations(&i, &r);
+        let comps = World::new().prepare_computations(&i, &r);
         assert!(comps.inside == false);
 
-        let r = Ray::new(Tuple::new_point(0.0, 0.0, 0.0), Tuple::new_vector(0.0, 0.0, 1.0));
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0));
         let i = Hitrecord { hit: 1.0, obj: &s };
-        let comps = World::prepare_computations(&i, &r);
+        let comps = World::new().prepare_computations(&i, &r);
 
-        assert!(comps.point == Tuple::new_point(0.0, 0.0, 1.0));
-        assert!(comps.eyev == Tuple::new_vector(0.0, 0.0, -1.0));
+        assert!(comps.point == Point3::new(0.0, 0.0, 1.0));
+        assert!(comps.eyev == Vector3::new(0.0, 0.0, -1.0));
         assert!(comps.inside == true);
-        assert!(comps.normalv == Tuple::new_vector(0.0, 0.0, -1.0));
+        assert!(comps.normalv.to_vector() == Vector3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_epsilon() {
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        let mut s = Object::new(Sphere::new());
+        let i = Hitrecord { hit: 4.0, obj: &s };
+        let mut w = World::new();
+        let comps = w.prepare_computations(&i, &r);
+        assert!(comps.offset == 0.01);
+        assert!(comps.over_point.eq_real(&Point3::new(0.0, 0.0, -1.01)));
+        // reflected rays leave on the eye side, refracted ones on the far side
+        assert!(comps.origin_towards(Vector3::new(0.0, 1.0, -1.0)) == comps.over_point);
+        assert!(comps.origin_towards(Vector3::new(0.0, 0.0, 1.0)).eq_real(&Point3::new(0.0, 0.0, -0.99)));
+
+        w.set_epsilon(Epsilon::Auto);
+        let near = w.prepare_computations(&i, &r).offset;
+        assert!(near > 0.0 && near < 0.01);
+
+        // further away and larger objects get a larger offset
+        let far = Ray::new(Point3::new(0.0, 0.0, -5000.0), Vector3::new(0.0, 0.0, 1.0));
+        assert!(w.prepare_computations(&Hitrecord { hit: 4999.0, obj: &s }, &far).offset > near * 100.0);
+        s.apply_transform(Matrix4::scale(1000.0, 1000.0, 1000.0)).unwrap();
+        let i = Hitrecord { hit: 0.0, obj: &s };
+        assert!(w.prepare_computations(&i, &r).offset > near * 100.0);
     }
 
     #[test]
@@ -163,18 +371,92 @@ mod tests {
         let mut w = World::new_default();
         w.objects[0].material.ambient = 1.0;
         w.objects[1].material.ambient = 1.0;
-        let r = Ray::new(Tuple::new_point(0.0, 0.0, 0.75), Tuple::new_vector(0.0, 0.0, -1.0));
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.75), Vector3::new(0.0, 0.0, -1.0));
         let c = w.color_at(&r);
-        assert!(c == w.objects[0].material.color);
+        // the outer sphere is hit behind the ray, so the inner one is seen
+        assert!(c == w.objects[1].material.color);
+    }
+
+    #[test]
+    fn test_shade_ray() {
+        let w = World::new_default();
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        let (c, hi) = w.shade_ray(&r);
+        let hi = hi.unwrap();
+        assert!(c == w.color_at(&r));
+        assert!(hi.hit == 4.0 && w.object_index(hi.obj) == Some(0));
+        assert!(w.object_index(&w.objects[1]) == Some(1));
+        assert!(w.object_index(&Object::new(Sphere::new())).is_none());
+
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 1.0, 0.0));
+        assert!(w.shade_ray(&r).1.is_none());
+    }
+
+    #[test]
+    fn test_color_at_packet() {
+        let w = World::new_default();
+        let rays = [
+            Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0)),
+            Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 1.0, 0.0)),
+            Ray::new(Point3::new(0.3, 0.2, -5.0), Vector3::new(0.0, 0.0, 1.0)),
+            Ray::new(Point3::new(-0.5, 0.6, -5.0), Vector3::new(0.1, -0.1, 1.0)),
+        ];
+        let colors = w.color_at_packet(&RayPacket::<F4>::new(&rays));
+        for (r, c) in rays.iter().zip(colors.iter()) {
+            let expected = w.color_at(r);
+            assert!((c.0 - expected.0).abs() < 1e-4 && (c.1 - expected.1).abs() < 1e-4 && (c.2 - expected.2).abs() < 1e-4);
+        }
+        assert!(colors[1] == Color::new(0.0, 0.0, 0.0));
+
+        // plain arrays as lanes give the same colors
+        assert!(w.color_at_packet(&RayPacket::<scalar::F4>::new(&rays)) == colors);
+    }
+
+    #[test]
+    fn test_closest_hit() {
+        let w = World::new_default();
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        let h = w.closest_hit(&r, Float::INFINITY).unwrap();
+        assert!(h.hit == 4.0 && std::ptr::eq(h.obj, &w.objects[0]));
+        assert!(w.closest_hit(&r, 4.0).is_none());
+        assert!(w.any_hit(&r, 4.5) && !w.any_hit(&r, 4.0));
+
+        let r = Ray::new(Point3::origin(), Vector3::new(0.0, 0.0, 1.0));
+        assert!(w.closest_hit(&r, Float::INFINITY).unwrap().hit == 0.5);
+
+        let mut buf = Vec::new();
+        w.intersect_world_into(&r, &mut buf);
+        w.intersect_world_into(&r, &mut buf);
+        assert!(buf.iter().map(|h| h.hit).collect::<Vec<Float>>() == vec![-1.0, -0.5, 0.5, 1.0]);
     }
 
     #[test]
     fn test_shadow() {
         let w = World::new_default();
-        let p = Tuple::new_point(0.0, 10.0, 0.0);
+        let p = Point3::new(0.0, 10.0, 0.0);
         assert!(w.is_shadowed(&p) == false);
 
-        let p = Tuple::new_point(10.0, -10.0, 10.0);
+        let p = Point3::new(10.0, -10.0, 10.0);
         assert!(w.is_shadowed(&p) == true);
     }
+
+    #[test]
+    fn test_light_filter() {
+        let mut w = World::new_default();
+        let p = Point3::new(10.0, -10.0, 10.0);
+        assert!(w.light_filter_at(&p, &w.lights[0], 0.0) == Color::new(0.0, 0.0, 0.0));
+        assert!(w.light_filter_at(&Point3::new(0.0, 10.0, 0.0), &w.lights[0], 0.0) == Color::new(1.0, 1.0, 1.0));
+
+        // both spheres are in the way, the outer one tints the light
+        w.objects[0].material.transparency = 0.5;
+        w.objects[1].material.transparency = 1.0;
+        assert!(w.light_filter_at(&p, &w.lights[0], 0.0) == Color::new(0.4, 0.5, 0.3));
+
+        w.objects[0].material.transparency = 0.0;
+        w.objects[0].material.casts_shadow = false;
+        assert!(w.light_filter_at(&p, &w.lights[0], 0.0) == Color::new(1.0, 1.0, 1.0));
+        assert!(w.is_shadowed_at(&p, &w.lights[0], 0.0));
+        w.objects[1].material.casts_shadow = false;
+        assert!(!w.is_shadowed_at(&p, &w.lights[0], 0.0));
+    }
 }
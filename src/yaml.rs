@@ -0,0 +1,451 @@
+// just enough yaml for scene files: block maps and sequences, flow [..] and {..},
+// plain and quoted scalars, and # comments. Every node remembers its line.
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Scalar(String),
+    Seq(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub value: Value,
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Node {
+    pub fn new(value: Value, line: usize) -> Self {
+        Self { value, line }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Node> {
+        match &self.value {
+            Value::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+fn error<T>(line: usize, message: &str) -> Result<T, ParseError> {
+    Err(ParseError { line, message: message.to_string() })
+}
+
+#[derive(Clone, Debug)]
+struct Line {
+    indent: usize,
+    text: String,
+    line: usize,
+}
+
+fn strip_comment(s: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+            continue
+        }
+        match (quote, c) {
+            (Some('"'), '\\') => escaped = true,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '#') if i == 0 || s[..i].ends_with(char::is_whitespace) => return &s[..i],
+            _ => (),
+        }
+    }
+    s
+}
+
+fn split_lines(src: &str) -> Result<Vec<Line>, ParseError> {
+    let mut lines = Vec::new();
+    for (n, raw) in src.lines().enumerate() {
+        if raw.trim_start().starts_with('\t') || raw.starts_with('\t') {
+            return error(n + 1, "tabs can't be used for indentation")
+        }
+        let text = strip_comment(raw).trim_end();
+        if text.trim().is_empty() || text.trim() == "---" {
+            continue
+        }
+        let indent = text.len() - text.trim_start().len();
+        lines.push(Line { indent, text: text.trim_start().to_string(), line: n + 1 });
+    }
+    Ok(lines)
+}
+
+fn bracket_depth(s: &str) -> i32 {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut depth = 0;
+    for c in s.chars() {
+        if escaped {
+            escaped = false;
+            continue
+        }
+        match (quote, c) {
+            (Some('"'), '\\') => escaped = true,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '[') | (None, '{') => depth += 1,
+            (None, ']') | (None, '}') => depth -= 1,
+            _ => (),
+        }
+    }
+    depth
+}
+
+// finds the colon of "key: value", if the line is a map entry
+fn split_key(s: &str) -> Option<(String, &str)> {
+    if s.starts_with('[') || s.starts_with('{') {
+        return None
+    }
+    if s.starts_with('"') || s.starts_with('\'') {
+        let q = s.chars().next().unwrap();
+        let mut escaped = false;
+        let end = s.char_indices().skip(1).find(|&(_, c)| {
+            let found = !escaped && c == q;
+            escaped = !escaped && q == '"' && c == '\\';
+            found
+        })?.0;
+        let rest = s[end + 1..].trim_start();
+        if rest == ":" || rest.starts_with(": ") {
+            return Some((s[1..end].to_string(), rest[1..].trim()))
+        }
+        return None
+    }
+    let i = s.find(": ").or_else(|| if s.ends_with(':') { Some(s.len() - 1) } else { None })?;
+    Some((s[..i].trim().to_string(), s[i + 1..].trim()))
+}
+
+struct Parser {
+    lines: Vec<Line>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Line> {
+        self.lines.get(self.pos)
+    }
+
+    fn block(&mut self, indent: usize) -> Result<Node, ParseError> {
+        let first = self.peek().unwrap().clone();
+        if first.text == "-" || first.text.starts_with("- ") {
+            self.seq(indent)
+        } else if split_key(&first.text).is_some() {
+            self.map(indent)
+        } else {
+            self.pos += 1;
+            let text = self.continued(first.text, first.line)?;
+            self.inline(text, first.line)
+        }
+    }
+
+    // flow collections can go on for several lines
+    fn continued(&mut self, mut text: String, line: usize) -> Result<String, ParseError> {
+        while bracket_depth(&text) > 0 {
+            match self.peek() {
+                Some(next) => {
+                    text.push(' ');
+                    text.push_str(&next.text);
+                    self.pos += 1;
+                },
+                None => return error(line, "unclosed bracket"),
+            }
+        }
+        Ok(text)
+    }
+
+    fn seq(&mut self, indent: usize) -> Result<Node, ParseError> {
+        let line = self.peek().unwrap().line;
+        let mut items = Vec::new();
+        while let Some(l) = self.peek() {
+            if l.indent < indent {
+                break
+            }
+            if l.indent > indent {
+                return error(l.line, "unexpected indentation")
+            }
+            if !(l.text == "-" || l.text.starts_with("- ")) {
+                return error(l.line, "expected a sequence item")
+            }
+
+            let rest = l.text[1..].trim_start().to_string();
+            if rest.is_empty() {
+                let item_line = l.line;
+                self.pos += 1;
+                match self.peek() {
+                    Some(next) if next.indent > indent => {
+                        let next_indent = next.indent;
+                        items.push(self.block(next_indent)?)
+                    },
+                    _ => items.push(Node::new(Value::Scalar(String::new()), item_line)),
+                }
+            } else {
+                // "- key: value" starts a map that continues on the next lines at the same column
+                let col = indent + (l.text.len() - rest.len());
+                self.lines[self.pos].indent = col;
+                self.lines[self.pos].text = rest;
+                items.push(self.block(col)?);
+            }
+        }
+        Ok(Node::new(Value::Seq(items), line))
+    }
+
+    fn map(&mut self, indent: usize) -> Result<Node, ParseError> {
+        let line = self.peek().unwrap().line;
+        let mut entries: Vec<(String, Node)> = Vec::new();
+        while let Some(l) = self.peek().cloned() {
+            if l.indent < indent {
+                break
+            }
+            if l.indent > indent {
+                return error(l.line, "unexpected indentation")
+            }
+            let (key, rest) = match split_key(&l.text) {
+                Some(kv) => kv,
+                None => return error(l.line, "expected 'key: value'"),
+            };
+            if entries.iter().any(|(k, _)| *k == key) {
+                return error(l.line, &format!("duplicate key '{}'", key))
+            }
+            self.pos += 1;
+
+            let value = if rest.is_empty() {
+                match self.peek() {
+                    Some(next) if next.indent > indent => {
+                        let next_indent = next.indent;
+                        self.block(next_indent)?
+                    },
+                    // sequences may sit at the same indentation as their key
+                    Some(next) if next.indent == indent && (next.text == "-" || next.text.starts_with("- ")) => {
+                        self.seq(indent)?
+                    },
+                    _ => Node::new(Value::Scalar(String::new()), l.line),
+                }
+            } else {
+                let text = self.continued(rest.to_string(), l.line)?;
+                self.inline(text, l.line)?
+            };
+            entries.push((key, value));
+        }
+        Ok(Node::new(Value::Map(entries), line))
+    }
+
+    fn inline(&mut self, text: String, line: usize) -> Result<Node, ParseError> {
+        let chars = text.chars().collect::<Vec<char>>();
+        let mut flow = Flow { chars, pos: 0, line };
+        let node = flow.value()?;
+        flow.skip_ws();
+        if flow.pos < flow.chars.len() {
+            return error(line, &format!("unexpected '{}'", flow.chars[flow.pos]))
+        }
+        Ok(node)
+    }
+}
+
+struct Flow {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Flow {
+    fn skip_ws(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn value(&mut self) -> Result<Node, ParseError> {
+        self.skip_ws();
+        match self.chars.get(self.pos) {
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_ws();
+                    if self.chars.get(self.pos) == Some(&']') {
+                        self.pos += 1;
+                        break
+                    }
+                    items.push(self.value()?);
+                    self.skip_ws();
+                    match self.chars.get(self.pos) {
+                        Some(',') => self.pos += 1,
+                        Some(']') => (),
+                        _ => return error(self.line, "expected ',' or ']'"),
+                    }
+                }
+                Ok(Node::new(Value::Seq(items), self.line))
+            },
+            Some('{') => {
+                self.pos += 1;
+                let mut entries = Vec::new();
+                loop {
+                    self.skip_ws();
+                    if self.chars.get(self.pos) == Some(&'}') {
+                        self.pos += 1;
+                        break
+                    }
+                    let key = match self.scalar(true)? {
+                        Node { value: Value::Scalar(s), .. } => s,
+                        _ => unreachable!(),
+                    };
+                    self.skip_ws();
+                    if self.chars.get(self.pos) != Some(&':') {
+                        return error(self.line, "expected ':'")
+                    }
+                    self.pos += 1;
+                    entries.push((key, self.value()?));
+                    self.skip_ws();
+                    match self.chars.get(self.pos) {
+                        Some(',') => self.pos += 1,
+                        Some('}') => (),
+                        _ => return error(self.line, "expected ',' or '}'"),
+                    }
+                }
+                Ok(Node::new(Value::Map(entries), self.line))
+            },
+            _ => self.scalar(false),
+        }
+    }
+
+    fn scalar(&mut self, is_key: bool) -> Result<Node, ParseError> {
+        self.skip_ws();
+        let start = self.pos;
+        match self.chars.get(self.pos) {
+            Some(&q) if q == '"' || q == '\'' => {
+                self.pos += 1;
+                let mut s = String::new();
+                loop {
+                    match self.chars.get(self.pos) {
+                        None => return error(self.line, "unclosed quote"),
+                        Some(&c) if c == q => { self.pos += 1; break },
+                        Some('\\') if q == '"' => {
+                            let c = match self.chars.get(self.pos + 1) {
+                                Some('n') => '\n',
+                                Some('t') => '\t',
+                                Some(&c) => c,
+                                None => return error(self.line, "unclosed quote"),
+                            };
+                            s.push(c);
+                            self.pos += 2;
+                        },
+                        Some(&c) => { s.push(c); self.pos += 1 },
+                    }
+                }
+                Ok(Node::new(Value::Scalar(s), self.line))
+            },
+            _ => {
+                while let Some(&c) = self.chars.get(self.pos) {
+                    if c == ',' || c == ']' || c == '}' || (is_key && c == ':') {
+                        break
+                    }
+                    self.pos += 1;
+                }
+                let s = self.chars[start..self.pos].iter().collect::<String>();
+                Ok(Node::new(Value::Scalar(s.trim().to_string()), self.line))
+            },
+        }
+    }
+}
+
+pub fn parse(src: &str) -> Result<Node, ParseError> {
+    let lines = split_lines(src)?;
+    if lines.is_empty() {
+        return Ok(Node::new(Value::Seq(Vec::new()), 1))
+    }
+    let indent = lines[0].indent;
+    let mut parser = Parser { lines, pos: 0 };
+    let node = parser.block(indent)?;
+    match parser.peek() {
+        Some(l) => error(l.line, "unexpected indentation"),
+        None => Ok(node),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scalar(s: &str) -> Value {
+        Value::Scalar(s.to_string())
+    }
+
+    #[test]
+    fn test_parse() {
+        let src = "
+# a comment
+- add: camera
+  width: 100
+  from: [ -6, 6, -10 ] # trailing comment
+
+- define: standard
+  value:
+    - [ translate, 1, -1, 1 ]
+    - [ scale, 0.5,
+        0.5, 0.5 ]
+- name: \"a # b\"
+  flow: { a: 1, b: [2, 3] }
+  list:
+  - x
+  - y
+";
+        let doc = parse(src).unwrap();
+        let items = match &doc.value {
+            Value::Seq(items) => items,
+            _ => panic!("not a sequence"),
+        };
+        assert!(items.len() == 3);
+
+        let camera = &items[0];
+        assert!(camera.line == 3);
+        assert!(camera.get("add").unwrap().value == scalar("camera"));
+        let from = camera.get("from").unwrap();
+        assert!(from.line == 5);
+        match &from.value {
+            Value::Seq(v) => assert!(v.iter().map(|n| n.value.clone()).collect::<Vec<Value>>() == vec![scalar("-6"), scalar("6"), scalar("-10")]),
+            _ => panic!("not a sequence"),
+        }
+
+        let value = items[1].get("value").unwrap();
+        match &value.value {
+            Value::Seq(v) => {
+                assert!(v.len() == 2);
+                assert!(v[1].line == 10);
+                match &v[1].value {
+                    Value::Seq(ops) => assert!(ops.len() == 4),
+                    _ => panic!("not a sequence"),
+                }
+            },
+            _ => panic!("not a sequence"),
+        }
+
+        assert!(items[2].get("name").unwrap().value == scalar("a # b"));
+        assert!(items[2].get("flow").unwrap().get("a").unwrap().value == scalar("1"));
+        match &items[2].get("list").unwrap().value {
+            Value::Seq(v) => assert!(v.len() == 2 && v[1].line == 16),
+            _ => panic!("not a sequence"),
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse("- a: 1\n   b: 2\n").unwrap_err().line == 2);
+        assert!(parse("a: [1, 2\n").unwrap_err().line == 1);
+        assert!(parse("a: 1\na: 2\n").unwrap_err().line == 2);
+    }
+}